@@ -0,0 +1,187 @@
+//! Arrow sudoku: the digits along the shaft of an arrow add up to the number in its circle.
+//! A circle spanning several positions (a pill) is read as a multi-digit number.
+
+use crate::constraint::{Constraint, Elimination};
+use crate::{Candidates, SudokuCandidates};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Arrow {
+    // circle positions followed by the shaft positions
+    positions: Vec<usize>,
+    circle_len: usize,
+}
+
+impl Arrow {
+    pub fn new(circle: Vec<usize>, shaft: Vec<usize>) -> Result<Arrow, String> {
+        if circle.is_empty() || shaft.is_empty() {
+            return Err("Arrow needs a circle and a shaft".to_owned());
+        }
+        if circle.len() > 3 {
+            return Err(format!(
+                "Circle of {} positions is too long, at most 3 are supported",
+                circle.len()
+            ));
+        }
+        let circle_len = circle.len();
+        let mut positions = circle;
+        positions.extend(shaft);
+        Ok(Arrow {
+            positions,
+            circle_len,
+        })
+    }
+
+    fn circle(&self) -> &[usize] {
+        &self.positions[..self.circle_len]
+    }
+
+    fn shaft(&self) -> &[usize] {
+        &self.positions[self.circle_len..]
+    }
+
+    /// Place value of each circle position, e.g. 10 and 1 for a pill of two positions.
+    fn place_values(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.circle_len)
+            .rev()
+            .map(|exponent| 10u32.pow(exponent as u32))
+    }
+}
+
+/// Smallest and largest digit of each position, None if a position has no candidates.
+fn digit_bounds(problem: &SudokuCandidates, positions: &[usize]) -> Option<Vec<(u32, u32)>> {
+    positions
+        .iter()
+        .map(|pos_idx| {
+            let cands = problem.get(*pos_idx);
+            Some((cands.first()? as u32, cands.last()? as u32))
+        })
+        .collect()
+}
+
+impl Constraint for Arrow {
+    fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+        let fixed: Vec<Option<u32>> = self
+            .positions
+            .iter()
+            .map(|pos_idx| {
+                let cands = problem.get(*pos_idx);
+                if cands.len() == 1 {
+                    cands.first().map(|digit| digit as u32)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let (circle, shaft) = fixed.split_at(self.circle_len);
+        let circle_value: Option<u32> = circle
+            .iter()
+            .zip(self.place_values())
+            .map(|(digit, place_value)| digit.map(|digit| digit * place_value))
+            .sum();
+        let circle_value = match circle_value {
+            Some(circle_value) => circle_value,
+            None => return false,
+        };
+        let shaft_sum: u32 = shaft.iter().flatten().sum();
+        shaft_sum > circle_value
+            || (shaft.iter().all(|digit| digit.is_some()) && shaft_sum != circle_value)
+    }
+
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+        let (circle_bounds, shaft_bounds) = match (
+            digit_bounds(problem, self.circle()),
+            digit_bounds(problem, self.shaft()),
+        ) {
+            (Some(circle_bounds), Some(shaft_bounds)) => (circle_bounds, shaft_bounds),
+            // contradiction, left to the regular elimination
+            _ => return vec![],
+        };
+        let place_values: Vec<u32> = self.place_values().collect();
+        let circle_min: u32 = circle_bounds
+            .iter()
+            .zip(&place_values)
+            .map(|((low, _), place_value)| low * place_value)
+            .sum();
+        let circle_max: u32 = circle_bounds
+            .iter()
+            .zip(&place_values)
+            .map(|((_, high), place_value)| high * place_value)
+            .sum();
+        let shaft_min: u32 = shaft_bounds.iter().map(|(low, _)| low).sum();
+        let shaft_max: u32 = shaft_bounds.iter().map(|(_, high)| high).sum();
+
+        let mut eliminations = vec![];
+        let mut eliminate = |pos_idx: usize, keep: &dyn Fn(u32) -> bool| {
+            let cands = problem.get(pos_idx);
+            let ruled_out = cands
+                .iter()
+                .filter(|digit| !keep(*digit as u32))
+                .fold(Candidates::NONE, |ruled_out, digit| {
+                    ruled_out | Candidates::single(digit)
+                });
+            if !ruled_out.is_empty() {
+                eliminations.push(Elimination {
+                    pos_idx,
+                    candidates: ruled_out,
+                });
+            }
+        };
+
+        // the circle has to be reachable by the shaft and the other way round
+        for ((pos_idx, (low, high)), place_value) in
+            self.circle().iter().zip(&circle_bounds).zip(&place_values)
+        {
+            let others_min = circle_min - low * place_value;
+            let others_max = circle_max - high * place_value;
+            eliminate(*pos_idx, &|digit| {
+                others_min + digit * place_value <= shaft_max
+                    && others_max + digit * place_value >= shaft_min
+            });
+        }
+        for (pos_idx, (low, high)) in self.shaft().iter().zip(&shaft_bounds) {
+            let others_min = shaft_min - low;
+            let others_max = shaft_max - high;
+            eliminate(*pos_idx, &|digit| {
+                others_min + digit <= circle_max && others_max + digit >= circle_min
+            });
+        }
+        eliminations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraint::remaining;
+
+    #[test]
+    fn test_prune_circle_and_shaft() {
+        let problem = crate::parse_sudoku(&"0".repeat(81)).unwrap();
+        // the circle is at least 1 + 1 + 1, each digit of the shaft at most 9 - 1 - 1
+        let arrow = Arrow::new(vec![0], vec![1, 2, 10]).unwrap();
+        assert_eq!(
+            remaining(&problem, &arrow),
+            vec![
+                vec![3, 4, 5, 6, 7, 8, 9],
+                vec![1, 2, 3, 4, 5, 6, 7],
+                vec![1, 2, 3, 4, 5, 6, 7],
+                vec![1, 2, 3, 4, 5, 6, 7],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prune_pill() {
+        let problem = crate::parse_sudoku(&"0".repeat(81)).unwrap();
+        // two shaft positions add up to at most 18, so the pill is 11 up to 18
+        let arrow = Arrow::new(vec![0, 1], vec![9, 18]).unwrap();
+        let remaining = remaining(&problem, &arrow);
+        assert_eq!(remaining[0], vec![1]);
+        assert_eq!(remaining[2], vec![2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(Arrow::new(vec![0], vec![]).is_err());
+    }
+}
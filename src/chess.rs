@@ -0,0 +1,79 @@
+//! Anti-knight and anti-king sudoku: positions a chess knight's or king's move apart
+//! must not contain the same digit.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ChessPiece {
+    Knight,
+    King,
+}
+
+impl ChessPiece {
+    /// Row and column offsets of the moves of the piece.
+    fn moves(self) -> &'static [(isize, isize)] {
+        match self {
+            ChessPiece::Knight => &[
+                (-2, -1),
+                (-2, 1),
+                (-1, -2),
+                (-1, 2),
+                (1, -2),
+                (1, 2),
+                (2, -1),
+                (2, 1),
+            ],
+            ChessPiece::King => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+/// Pairs of positions of a `size` x `size` grid a move of the piece apart, each pair once.
+pub(crate) fn move_pairs(piece: ChessPiece, size: usize) -> Vec<[usize; 2]> {
+    let mut pairs = vec![];
+    for pos_idx in 0..size * size {
+        let (row_idx, col_idx) = ((pos_idx / size) as isize, (pos_idx % size) as isize);
+        for (row_offset, col_offset) in piece.moves() {
+            let (row, col) = (row_idx + row_offset, col_idx + col_offset);
+            if !(0..size as isize).contains(&row) || !(0..size as isize).contains(&col) {
+                continue;
+            }
+            let target = row as usize * size + col as usize;
+            if target > pos_idx {
+                pairs.push([pos_idx, target]);
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_pairs() {
+        let knight = move_pairs(ChessPiece::Knight, 9);
+        let targets_of = |pairs: &[[usize; 2]], pos_idx: usize| {
+            pairs.iter().filter(|pair| pair.contains(&pos_idx)).count()
+        };
+        // corner, edge and center
+        assert_eq!(targets_of(&knight, 0), 2);
+        assert!(knight.contains(&[0, 11]) && knight.contains(&[0, 19]));
+        assert_eq!(targets_of(&knight, 4), 4);
+        assert_eq!(targets_of(&knight, 40), 8);
+
+        let king = move_pairs(ChessPiece::King, 9);
+        assert_eq!(targets_of(&king, 0), 3);
+        assert_eq!(targets_of(&king, 40), 8);
+        // 8 rows with 9 vertical pairs, 9 rows with 8 horizontal pairs, 2 * 8 * 8 diagonal pairs
+        assert_eq!(king.len(), 72 + 72 + 128);
+    }
+}
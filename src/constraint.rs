@@ -0,0 +1,142 @@
+//! Rules of a puzzle, e.g. "every digit once per house" or the cages of a killer sudoku.
+//! Houses are built in, further rules can be added to a layout with `Layout::with_constraint`.
+
+use std::fmt;
+
+use crate::layout::House;
+use crate::{has_duplicate_singles, Candidates, SudokuCandidates};
+
+/// Candidates to be removed from a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elimination {
+    pub pos_idx: usize,
+    pub candidates: Candidates,
+}
+
+/// Rule of a puzzle, consulted by the solver during constraint propagation.
+pub trait Constraint: fmt::Debug + Send + Sync {
+    /// Positions the constraint depends on.
+    fn positions(&self) -> &[usize];
+
+    /// True if the positions with a single remaining candidate already violate the constraint.
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool;
+
+    /// Candidates that cannot be part of any solution satisfying the constraint.
+    /// Removing all candidates of a position signals a contradiction.
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination>;
+}
+
+/// Every digit once per house. The solver prunes the houses like any other rule during
+/// propagation. A placed digit is also removed from its peers right away as a fast path, so
+/// pruning a house only finds digits that were placed without it.
+impl Constraint for House {
+    fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+        has_duplicate_singles(self.positions.iter().map(|pos_idx| problem.get(*pos_idx)))
+    }
+
+    /// Removes placed digits from the other positions and places digits that fit into a
+    /// single position of the house (hidden singles).
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+        let size = problem.layout().size();
+        let mut placed = Candidates::NONE;
+        // digits fitting into at least one and into at least two positions
+        let mut once = Candidates::NONE;
+        let mut twice = Candidates::NONE;
+        for pos_idx in &self.positions {
+            let cands = problem.get(*pos_idx);
+            if cands.len() == 1 {
+                placed = placed | cands;
+            }
+            twice = twice | (once & cands);
+            once = once | cands;
+        }
+        // a house with fewer positions than digits does not need to hold all of them
+        let hidden = if self.positions.len() == size {
+            once.without(twice).without(placed)
+        } else {
+            Candidates::NONE
+        };
+        self.positions
+            .iter()
+            .filter_map(|pos_idx| {
+                let cands = problem.get(*pos_idx);
+                let ruled_out = match (cands & hidden).len() {
+                    _ if cands.len() == 1 => Candidates::NONE,
+                    0 => cands & placed,
+                    // two digits of the house only fit here: elimination leaves nothing
+                    1 => cands.without(hidden),
+                    _ => cands,
+                };
+                if ruled_out.is_empty() {
+                    None
+                } else {
+                    Some(Elimination {
+                        pos_idx: *pos_idx,
+                        candidates: ruled_out,
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+/// Remaining candidates of the positions of the constraint after applying its eliminations
+/// once, to check `prune` in tests.
+#[cfg(test)]
+pub(crate) fn remaining(problem: &SudokuCandidates, constraint: &dyn Constraint) -> Vec<Vec<u8>> {
+    let mut problem = problem.clone();
+    for elimination in constraint.prune(&problem) {
+        let cands = problem.get(elimination.pos_idx);
+        problem.set(elimination.pos_idx, cands.without(elimination.candidates));
+    }
+    constraint
+        .positions()
+        .iter()
+        .map(|pos_idx| problem.get(*pos_idx).iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_house_constraint() {
+        // 1 and 2 in the first row
+        let problem = crate::parse_sudoku(&("12".to_owned() + &"0".repeat(79))).unwrap();
+        let row = &problem.layout().houses()[0];
+        assert_eq!(row.positions().len(), 9);
+        assert!(!row.has_conflict(&problem));
+        // the digits were already eliminated while parsing
+        assert!(row.prune(&problem).is_empty());
+
+        let mut unpropagated = problem.clone();
+        unpropagated.set(2, Candidates::all(9));
+        assert_eq!(
+            row.prune(&unpropagated),
+            vec![Elimination {
+                pos_idx: 2,
+                candidates: Candidates::single(1) | Candidates::single(2),
+            }]
+        );
+        unpropagated.set(2, Candidates::single(1));
+        assert!(row.has_conflict(&unpropagated));
+
+        // 9 only fits into r1c9 of the row
+        let mut hidden = problem.clone();
+        for pos_idx in 2..8 {
+            hidden.set(pos_idx, hidden.get(pos_idx).without(Candidates::single(9)));
+        }
+        assert_eq!(
+            row.prune(&hidden),
+            vec![Elimination {
+                pos_idx: 8,
+                candidates: hidden.get(8).without(Candidates::single(9)),
+            }]
+        );
+    }
+}
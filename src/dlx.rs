@@ -0,0 +1,423 @@
+//! Exact cover solver based on Knuth's Dancing Links (Algorithm X).
+//!
+//! Every possible placement (position, digit) is a row of the exact cover matrix,
+//! every constraint ("position filled", "digit once per house") is a column.
+//! A solution is a set of placements covering each constraint exactly once.
+
+use std::sync::Arc;
+
+use crate::{Candidates, SolutionCount, SolveStats, Solver, SudokuCandidates};
+
+/// Sparse exact cover matrix as circular doubly linked lists.
+/// Node 0 is the root, nodes `1..=num_constraints` are the column headers,
+/// the remaining nodes are the ones of the matrix.
+struct DancingLinks {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    // placement index (pos_idx * size + digit - 1) of the matrix row a node belongs to
+    placement: Vec<usize>,
+    // number of nodes left in each column, indexed by header node
+    size: Vec<usize>,
+}
+
+impl DancingLinks {
+    fn new(num_constraints: usize, num_nodes: usize) -> DancingLinks {
+        let num_headers = num_constraints + 1;
+        let mut links = DancingLinks {
+            left: (0..num_headers)
+                .map(|i| (i + num_headers - 1) % num_headers)
+                .collect(),
+            right: (0..num_headers).map(|i| (i + 1) % num_headers).collect(),
+            up: (0..num_headers).collect(),
+            down: (0..num_headers).collect(),
+            column: (0..num_headers).collect(),
+            placement: vec![usize::MAX; num_headers],
+            size: vec![0; num_headers],
+        };
+        links.reserve(num_nodes);
+        links
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.left.reserve(additional);
+        self.right.reserve(additional);
+        self.up.reserve(additional);
+        self.down.reserve(additional);
+        self.column.reserve(additional);
+        self.placement.reserve(additional);
+    }
+
+    /// Appends a matrix row with a node in each of the given constraint columns (0 based).
+    fn add_row(&mut self, placement: usize, constraints: &[usize]) {
+        let first = self.left.len();
+        for (i, constraint) in constraints.iter().enumerate() {
+            let node = first + i;
+            let header = constraint + 1;
+            self.left.push(if i == 0 {
+                first + constraints.len() - 1
+            } else {
+                node - 1
+            });
+            self.right.push(if i + 1 == constraints.len() {
+                first
+            } else {
+                node + 1
+            });
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            let last_in_column = self.up[header];
+            self.down[last_in_column] = node;
+            self.up[header] = node;
+            self.column.push(header);
+            self.placement.push(placement);
+            self.size[header] += 1;
+        }
+    }
+
+    fn cover(&mut self, header: usize) {
+        self.right[self.left[header]] = self.right[header];
+        self.left[self.right[header]] = self.left[header];
+        let mut row_node = self.down[header];
+        while row_node != header {
+            let mut node = self.right[row_node];
+            while node != row_node {
+                self.down[self.up[node]] = self.down[node];
+                self.up[self.down[node]] = self.up[node];
+                self.size[self.column[node]] -= 1;
+                node = self.right[node];
+            }
+            row_node = self.down[row_node];
+        }
+    }
+
+    fn uncover(&mut self, header: usize) {
+        let mut row_node = self.up[header];
+        while row_node != header {
+            let mut node = self.left[row_node];
+            while node != row_node {
+                self.size[self.column[node]] += 1;
+                self.down[self.up[node]] = node;
+                self.up[self.down[node]] = node;
+                node = self.left[node];
+            }
+            row_node = self.up[row_node];
+        }
+        self.right[self.left[header]] = header;
+        self.left[self.right[header]] = header;
+    }
+
+    /// Column with the fewest remaining nodes, None if all columns are covered.
+    fn choose_column(&self) -> Option<usize> {
+        let mut best = None;
+        let mut best_size = usize::MAX;
+        let mut header = self.right[0];
+        while header != 0 {
+            if self.size[header] < best_size {
+                best = Some(header);
+                best_size = self.size[header];
+            }
+            header = self.right[header];
+        }
+        best
+    }
+
+    /// Algorithm X, stops once `result.limit` solutions were found.
+    fn search(&mut self, partial: &mut Vec<usize>, result: &mut SearchResult) {
+        let header = match self.choose_column() {
+            Some(header) => header,
+            None => {
+                if result.first.is_none() {
+                    result.first = Some(partial.clone());
+                }
+                result.num_solutions += 1;
+                return;
+            }
+        };
+        if self.size[header] == 0 {
+            result.stats.dead_ends += 1;
+            return;
+        }
+
+        self.cover(header);
+        let mut row_node = self.down[header];
+        while row_node != header && result.num_solutions < result.limit {
+            let placement = self.placement[row_node];
+            result.stats.nodes += 1;
+            let trail_len = result.constraint_check.place(placement);
+            if result.constraint_check.is_consistent(placement, trail_len) {
+                partial.push(placement);
+                let mut node = self.right[row_node];
+                while node != row_node {
+                    self.cover(self.column[node]);
+                    node = self.right[node];
+                }
+
+                self.search(partial, result);
+
+                let mut node = self.left[row_node];
+                while node != row_node {
+                    self.uncover(self.column[node]);
+                    node = self.left[node];
+                }
+                partial.pop();
+            } else {
+                result.stats.dead_ends += 1;
+            }
+            result.constraint_check.unplace(trail_len);
+            row_node = self.down[row_node];
+        }
+        self.uncover(header);
+    }
+}
+
+struct SearchResult {
+    first: Option<Vec<usize>>,
+    num_solutions: usize,
+    limit: usize,
+    stats: SolveStats,
+    constraint_check: ConstraintCheck,
+}
+
+/// Checks the additional constraints of the layout (e.g. killer cages), which are not part of
+/// the exact cover matrix, against the placements chosen so far.
+struct ConstraintCheck {
+    // problem with the placements applied and their digits removed from the peers
+    problem: SudokuCandidates,
+    // indices into the constraints of the layout for each position
+    constraints_of_pos: Vec<Vec<usize>>,
+    // previous candidates of the modified positions, to undo placements
+    trail: Vec<(usize, Candidates)>,
+    // false for layouts without additional constraints, which need no checks at all
+    active: bool,
+}
+
+impl ConstraintCheck {
+    fn new(problem: &SudokuCandidates) -> ConstraintCheck {
+        let layout = problem.layout();
+        let mut constraints_of_pos = vec![vec![]; problem.grid.len()];
+        for (constraint_idx, constraint) in layout.constraints().iter().enumerate() {
+            for pos_idx in constraint.positions() {
+                constraints_of_pos[*pos_idx].push(constraint_idx);
+            }
+        }
+        ConstraintCheck {
+            problem: problem.clone(),
+            constraints_of_pos,
+            trail: vec![],
+            active: layout.has_peer_groups() || !layout.constraints().is_empty(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.problem.layout().size()
+    }
+
+    /// Fixes the digit of the placement and removes it from the peers.
+    /// Returns the length of the trail before, to be passed to `unplace`.
+    fn place(&mut self, placement: usize) -> usize {
+        let trail_len = self.trail.len();
+        if !self.active {
+            return trail_len;
+        }
+        let pos_idx = placement / self.size();
+        let digit = (placement % self.size()) as u8 + 1;
+        self.trail.push((pos_idx, self.problem.get(pos_idx)));
+        self.problem.set(pos_idx, Candidates::single(digit));
+        let layout = Arc::clone(&self.problem.layout);
+        for peer_idx in layout.peers(pos_idx) {
+            let mut cands = self.problem.get(*peer_idx);
+            // single peers are placed already or conflicting, see `is_consistent`
+            if cands.len() > 1 && cands.contains(digit) {
+                self.trail.push((*peer_idx, cands));
+                cands.remove(digit);
+                self.problem.set(*peer_idx, cands);
+            }
+        }
+        trail_len
+    }
+
+    fn unplace(&mut self, trail_len: usize) {
+        while self.trail.len() > trail_len {
+            let (pos_idx, cands) = self.trail.pop().unwrap();
+            self.problem.set(pos_idx, cands);
+        }
+    }
+
+    /// True if the constraints depending on the positions modified by the placement
+    /// (starting at `trail_len`) can still be satisfied.
+    fn is_consistent(&self, placement: usize, trail_len: usize) -> bool {
+        if !self.active {
+            return true;
+        }
+        let layout = self.problem.layout();
+        let pos_idx = placement / self.size();
+        // peers outside of the houses, e.g. anti-knight pairs, are not part of the matrix either
+        if layout.has_peer_groups() {
+            let cands = self.problem.get(pos_idx);
+            if layout
+                .peers(pos_idx)
+                .iter()
+                .any(|peer_idx| self.problem.get(*peer_idx) == cands)
+            {
+                return false;
+            }
+        }
+        // constraints of the position and of the peers that lost the digit
+        let constraints = layout.constraints();
+        let mut checked: Vec<usize> = vec![];
+        for (modified_idx, _) in &self.trail[trail_len..] {
+            for constraint_idx in &self.constraints_of_pos[*modified_idx] {
+                if checked.contains(constraint_idx) {
+                    continue;
+                }
+                checked.push(*constraint_idx);
+                let constraint = &constraints[*constraint_idx];
+                if constraint.has_conflict(&self.problem)
+                    || constraint.prune(&self.problem).iter().any(|elimination| {
+                        self.problem
+                            .get(elimination.pos_idx)
+                            .without(elimination.candidates)
+                            .is_empty()
+                    })
+                {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Builds the exact cover matrix with a row for each remaining candidate of the problem.
+fn build_matrix(problem: &SudokuCandidates) -> DancingLinks {
+    let layout = problem.layout();
+    let size = layout.size();
+    let num_positions = layout.num_positions();
+    let num_constraints = num_positions + layout.houses().len() * size;
+    let num_nodes = (0..num_positions)
+        .map(|pos_idx| problem.get(pos_idx).len() * (1 + layout.houses_of(pos_idx).count()))
+        .sum();
+    let mut links = DancingLinks::new(num_constraints, num_nodes);
+
+    let mut constraints = vec![];
+    for pos_idx in 0..num_positions {
+        for digit in problem.get(pos_idx).iter() {
+            let digit_idx = digit as usize - 1;
+            constraints.clear();
+            constraints.push(pos_idx);
+            for house_idx in layout.house_indices_of(pos_idx) {
+                constraints.push(num_positions + house_idx * size + digit_idx);
+            }
+            links.add_row(pos_idx * size + digit_idx, &constraints);
+        }
+    }
+    links
+}
+
+fn search(problem: &SudokuCandidates, limit: usize) -> SearchResult {
+    let mut result = SearchResult {
+        first: None,
+        num_solutions: 0,
+        limit,
+        stats: SolveStats::default(),
+        constraint_check: ConstraintCheck::new(problem),
+    };
+    build_matrix(problem).search(&mut vec![], &mut result);
+    result
+}
+
+fn placements_to_numbers(placements: &[usize], size: usize, num_positions: usize) -> Vec<u8> {
+    let mut numbers = vec![0; num_positions];
+    for placement in placements {
+        numbers[placement / size] = (placement % size) as u8 + 1;
+    }
+    numbers
+}
+
+/// Solves the problem given in the format of `SudokuCandidates::from_vec` using Dancing Links.
+/// Returns None if the problem has no solution.
+pub fn solve_sudoku_dlx(numbers: Vec<u8>) -> Option<SudokuCandidates> {
+    SudokuCandidates::from_vec(numbers).and_then(|problem| DlxSolver.solve(&problem))
+}
+
+/// Exact cover solver, see `solve_sudoku_dlx`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DlxSolver;
+
+impl Solver for DlxSolver {
+    fn count_solutions(&self, problem: &SudokuCandidates, limit: usize) -> SolutionCount {
+        SolutionCount::from_limited(search(problem, limit).num_solutions, limit)
+    }
+
+    fn solve_with_stats(
+        &self,
+        problem: &SudokuCandidates,
+    ) -> (Option<SudokuCandidates>, SolveStats) {
+        let result = search(problem, 1);
+        let layout = problem.layout();
+        let solution = result.first.and_then(|placements| {
+            SudokuCandidates::from_vec_with_layout(
+                Arc::clone(&problem.layout),
+                placements_to_numbers(&placements, layout.size(), layout.num_positions()),
+            )
+        });
+        (solution, result.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{convert_problem_str, solve_sudoku};
+
+    #[test]
+    fn test_dlx_solves_hard_problem() {
+        let sudoku_vec = convert_problem_str(
+            "800000000003600000070090200050007000000045700000100030001000068008500010090000400",
+        );
+        let solution = solve_sudoku_dlx(sudoku_vec).unwrap();
+        assert!(solution.is_correct());
+        assert_eq!(
+            solution.to_continuous_string(),
+            "812753649943682175675491283154237896369845721287169534521974368438526917796318452"
+        );
+    }
+
+    #[test]
+    fn test_dlx_matches_backtracking() {
+        let problem_str =
+            "100007860007008010800200009000000002400010000009005000608000000000050900000009304";
+        let dlx_solution = solve_sudoku_dlx(convert_problem_str(problem_str)).unwrap();
+        let bt_solution = solve_sudoku(
+            SudokuCandidates::from_vec(convert_problem_str(problem_str)),
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            dlx_solution.to_continuous_string(),
+            bt_solution.to_continuous_string()
+        );
+    }
+
+    #[test]
+    fn test_dlx_trivial_problem_is_solvable() {
+        assert!(solve_sudoku_dlx(vec![0; 81]).unwrap().is_correct());
+    }
+
+    #[test]
+    fn test_dlx_solves_16x16_problem() {
+        let solution = solve_sudoku_dlx(convert_problem_str(&"0".repeat(256))).unwrap();
+        assert!(solution.is_correct());
+    }
+
+    #[test]
+    fn test_dlx_wrong_problem_is_unsolvable() {
+        let sudoku_vec = convert_problem_str(
+            "110000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        );
+        assert!(solve_sudoku_dlx(sudoku_vec).is_none());
+    }
+}
@@ -0,0 +1,380 @@
+//! Fish: if a digit of N rows (the base sets) can only go into N columns (the cover sets),
+//! the digit takes up these columns and is removed from their other positions. The same holds
+//! with rows and columns swapped. X-Wing, Swordfish and Jellyfish have 2, 3 and 4 base sets.
+//! A finned fish has a few extra candidates (the fins) outside of the cover sets, all within
+//! one box. Either a fin or the fish holds the digit, so only positions of the cover sets that
+//! also see the box of the fins lose the digit. If a base set has just one candidate in the
+//! cover sets left, the fish is called sashimi.
+
+use crate::intersections::is_box;
+use crate::layout::{House, HouseKind};
+use crate::logic::{eliminations_from, Action, Step, Technique};
+use crate::subsets::{combinations, locked_sets};
+use crate::{Candidates, SudokuCandidates};
+
+/// Largest fish searched for (Jellyfish). A cap on cost: the line combinations to try grow
+/// quickly with the size, and larger fish are rarely needed.
+const MAX_FISH_SIZE: usize = 4;
+
+fn bits(mask: u32) -> impl Iterator<Item = usize> {
+    (0..32).filter(move |bit| mask & 1 << bit != 0)
+}
+
+/// Rows and columns of a single grid, ordered by their index.
+struct Lines {
+    rows: Vec<usize>,
+    columns: Vec<usize>,
+}
+
+fn lines_of_grid(houses: &[House], grid: Option<usize>) -> Lines {
+    let mut rows = vec![];
+    let mut columns = vec![];
+    for (house_idx, house) in houses.iter().enumerate() {
+        if house.grid != grid {
+            continue;
+        }
+        match house.kind {
+            HouseKind::Row(idx) => rows.push((idx, house_idx)),
+            HouseKind::Column(idx) => columns.push((idx, house_idx)),
+            _ => {}
+        }
+    }
+    rows.sort_unstable();
+    columns.sort_unstable();
+    Lines {
+        rows: rows.into_iter().map(|(_, house_idx)| house_idx).collect(),
+        columns: columns
+            .into_iter()
+            .map(|(_, house_idx)| house_idx)
+            .collect(),
+    }
+}
+
+/// Search for fish of one digit with the given base and cover sets. The i-th position of a
+/// base set lies in the i-th cover set and the other way round.
+struct FishSearch<'a> {
+    problem: &'a SudokuCandidates,
+    digit: u8,
+    base: &'a [usize],
+    cover: &'a [usize],
+    // bit i is set if the digit fits into the position of the base set in the i-th cover set,
+    // zero for base sets where the digit is placed already
+    masks: Vec<u32>,
+}
+
+impl<'a> FishSearch<'a> {
+    fn new(
+        problem: &'a SudokuCandidates,
+        digit: u8,
+        base: &'a [usize],
+        cover: &'a [usize],
+    ) -> FishSearch<'a> {
+        let houses = problem.layout().houses();
+        let masks = base
+            .iter()
+            .map(|house_idx| {
+                let positions = &houses[*house_idx].positions;
+                let solved = positions
+                    .iter()
+                    .any(|pos_idx| problem.get(*pos_idx) == Candidates::single(digit));
+                if solved {
+                    return 0;
+                }
+                positions
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pos_idx)| problem.get(**pos_idx).contains(digit))
+                    .fold(0, |mask, (idx, _)| mask | 1 << idx)
+            })
+            .collect();
+        FishSearch {
+            problem,
+            digit,
+            base,
+            cover,
+            masks,
+        }
+    }
+
+    fn position(&self, base_idx: usize, cover_idx: usize) -> usize {
+        self.problem.layout().houses()[self.base[base_idx]].positions[cover_idx]
+    }
+
+    /// Positions of the base sets holding the digit.
+    fn candidates(&self, chosen: &[usize]) -> Vec<usize> {
+        let mut positions: Vec<usize> = chosen
+            .iter()
+            .flat_map(|base_idx| {
+                bits(self.masks[*base_idx])
+                    .map(move |cover_idx| self.position(*base_idx, cover_idx))
+            })
+            .collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    fn step(
+        &self,
+        chosen: &[usize],
+        covers: &[usize],
+        fins: Vec<usize>,
+        sashimi: bool,
+        mut targets: Vec<usize>,
+    ) -> Option<Step> {
+        targets.sort_unstable();
+        let eliminations = eliminations_from(self.problem, targets, Candidates::single(self.digit));
+        if eliminations.is_empty() {
+            return None;
+        }
+        let houses = chosen
+            .iter()
+            .map(|base_idx| self.base[*base_idx])
+            .chain(covers.iter().map(|cover_idx| self.cover[*cover_idx]))
+            .collect();
+        Some(Step {
+            technique: Technique::Fish {
+                size: chosen.len(),
+                fins,
+                sashimi,
+            },
+            positions: self.candidates(chosen),
+            houses,
+            action: Action::Eliminate(eliminations),
+        })
+    }
+
+    /// Positions of the cover sets outside of the chosen base sets.
+    fn cover_positions<'b>(
+        &'b self,
+        chosen: &'b [usize],
+        covers: &'b [usize],
+    ) -> impl Iterator<Item = usize> + 'b {
+        let houses = self.problem.layout().houses();
+        covers.iter().flat_map(move |cover_idx| {
+            houses[self.cover[*cover_idx]]
+                .positions
+                .iter()
+                .enumerate()
+                .filter(move |(base_idx, _)| !chosen.contains(base_idx))
+                .map(|(_, pos_idx)| *pos_idx)
+        })
+    }
+
+    fn basic(&self, size: usize) -> Option<Step> {
+        let mut step = None;
+        locked_sets(&self.masks, size, &mut |chosen, union| {
+            let covers: Vec<usize> = bits(union).collect();
+            let targets: Vec<usize> = self.cover_positions(chosen, &covers).collect();
+            step = self.step(chosen, &covers, vec![], false, targets);
+            step.is_some()
+        });
+        step
+    }
+
+    fn finned(&self, size: usize) -> Option<Step> {
+        let layout = self.problem.layout();
+        // the fins are in a single box, so they add at most the width of a box to the covers
+        let max_width = size + layout.cell_rows().max(layout.cell_cols());
+        let eligible: Vec<usize> = (0..self.masks.len())
+            .filter(|base_idx| {
+                let len = self.masks[*base_idx].count_ones() as usize;
+                len > 0 && len <= max_width
+            })
+            .collect();
+        let mut step = None;
+        combinations(
+            eligible.len(),
+            size,
+            (),
+            |_, _| Some(()),
+            |combination, _| {
+                let chosen: Vec<usize> = combination.iter().map(|idx| eligible[*idx]).collect();
+                let union = chosen
+                    .iter()
+                    .fold(0, |union, base_idx| union | self.masks[*base_idx]);
+                let union_bits: Vec<usize> = bits(union).collect();
+                if union_bits.len() <= size || union_bits.len() > max_width {
+                    return false;
+                }
+                combinations(
+                    union_bits.len(),
+                    size,
+                    (),
+                    |_, _| Some(()),
+                    |cover_combination, _| {
+                        let covers: Vec<usize> = cover_combination
+                            .iter()
+                            .map(|idx| union_bits[*idx])
+                            .collect();
+                        let cover_mask = covers
+                            .iter()
+                            .fold(0, |mask, cover_idx| mask | 1 << cover_idx);
+                        if chosen
+                            .iter()
+                            .any(|base_idx| self.masks[*base_idx] & cover_mask == 0)
+                        {
+                            return false;
+                        }
+                        let fins: Vec<usize> = chosen
+                            .iter()
+                            .flat_map(|base_idx| {
+                                bits(self.masks[*base_idx] & !cover_mask)
+                                    .map(move |cover_idx| self.position(*base_idx, cover_idx))
+                            })
+                            .collect();
+                        let fin_box = match layout
+                            .houses_of(fins[0])
+                            .find(|house| is_box(house))
+                            .filter(|fin_box| {
+                                fins.iter().all(|fin| fin_box.positions.contains(fin))
+                            }) {
+                            Some(fin_box) => fin_box,
+                            None => return false,
+                        };
+                        let sashimi = chosen
+                            .iter()
+                            .any(|base_idx| (self.masks[*base_idx] & cover_mask).count_ones() == 1);
+                        let targets: Vec<usize> = self
+                            .cover_positions(&chosen, &covers)
+                            .filter(|pos_idx| fin_box.positions.contains(pos_idx))
+                            .collect();
+                        step = self.step(&chosen, &covers, fins, sashimi, targets);
+                        step.is_some()
+                    },
+                )
+            },
+        );
+        step
+    }
+}
+
+/// First fish in any grid, smaller fish first and basic before finned ones of the same size.
+/// Rows are tried as base sets before columns.
+pub(crate) fn find_fish(problem: &SudokuCandidates) -> Option<Step> {
+    let layout = problem.layout();
+    let houses = layout.houses();
+    let mut grids: Vec<Option<usize>> = houses.iter().map(|house| house.grid).collect();
+    grids.sort_unstable();
+    grids.dedup();
+    let lines: Vec<Lines> = grids
+        .into_iter()
+        .map(|grid| lines_of_grid(houses, grid))
+        .collect();
+    let searches: Vec<FishSearch> = Candidates::all(layout.size())
+        .iter()
+        .flat_map(|digit| {
+            lines.iter().flat_map(move |lines| {
+                vec![
+                    FishSearch::new(problem, digit, &lines.rows, &lines.columns),
+                    FishSearch::new(problem, digit, &lines.columns, &lines.rows),
+                ]
+            })
+        })
+        .collect();
+    for size in 2..=MAX_FISH_SIZE {
+        if let Some(step) = searches.iter().find_map(|search| search.basic(size)) {
+            return Some(step);
+        }
+        if let Some(step) = searches.iter().find_map(|search| search.finned(size)) {
+            return Some(step);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty grid where the digit 1 is ruled out at the given positions.
+    fn without_ones(positions: impl Iterator<Item = usize>) -> SudokuCandidates {
+        let no_one: &[u8] = &[2, 3, 4, 5, 6, 7, 8, 9];
+        let marks: Vec<(usize, &[u8])> = positions.map(|pos_idx| (pos_idx, no_one)).collect();
+        SudokuCandidates::pencil_marks(&marks)
+    }
+
+    /// Empty grid where the digit 1 is only possible at the given positions of the rows.
+    fn rows_with_ones(rows: &[(usize, &[usize])]) -> SudokuCandidates {
+        without_ones(rows.iter().flat_map(|(row_idx, cols)| {
+            (0..9)
+                .filter(move |col_idx| !cols.contains(col_idx))
+                .map(move |col_idx| row_idx * 9 + col_idx)
+        }))
+    }
+
+    #[test]
+    fn test_x_wing() {
+        let problem = rows_with_ones(&[(1, &[1, 7]), (5, &[1, 7])]);
+        let step = find_fish(&problem).unwrap();
+        assert_eq!(step.base_sets(), &[1, 5]);
+        assert_eq!(step.cover_sets(), &[10, 16]);
+        assert!(step.fins().is_empty());
+        assert_eq!(step.positions, vec![10, 16, 46, 52]);
+        let description = step.describe(problem.layout());
+        assert!(description.starts_with(
+            "X-Wing in row 2 and row 6 covering column 2 and column 8: r1c2 <> 1, r1c8 <> 1, \
+             r3c2 <> 1, r3c8 <> 1, r4c2 <> 1"
+        ));
+        match step.action {
+            Action::Eliminate(eliminations) => assert_eq!(eliminations.len(), 14),
+            action => panic!("unexpected action {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_swordfish() {
+        // columns as base sets: the 1 of columns 1, 5 and 9 fits into rows 2, 4 and 8 only
+        let columns = [(0, [1, 3]), (4, [3, 7]), (8, [1, 7])];
+        let problem = without_ones(columns.iter().flat_map(|(col_idx, rows)| {
+            (0..9)
+                .filter(move |row_idx| !rows.contains(row_idx))
+                .map(move |row_idx| row_idx * 9 + col_idx)
+        }));
+        let step = find_fish(&problem).unwrap();
+        assert_eq!(
+            step.technique,
+            Technique::Fish {
+                size: 3,
+                fins: vec![],
+                sashimi: false
+            }
+        );
+        assert_eq!(step.base_sets(), &[9, 13, 17]);
+        assert_eq!(step.cover_sets(), &[1, 3, 7]);
+        assert_eq!(step.technique.to_string(), "Swordfish");
+    }
+
+    #[test]
+    fn test_finned_x_wing() {
+        // the 1 of row 2 also fits into r2c9, which is in the box of r2c8
+        let problem = rows_with_ones(&[(1, &[1, 7, 8]), (5, &[1, 7])]);
+        let step = find_fish(&problem).unwrap();
+        assert_eq!(step.fins(), &[17]);
+        // only r1c8 and r3c8 see both the fish and the fin
+        assert_eq!(
+            step.describe(problem.layout()),
+            "finned X-Wing in row 2 and row 6 covering column 2 and column 8 with fin r2c9: \
+             r1c8 <> 1, r3c8 <> 1"
+        );
+    }
+
+    #[test]
+    fn test_sashimi_x_wing() {
+        // r2c8 cannot hold the 1, leaving r2c2 as its only position in the cover sets
+        let problem = rows_with_ones(&[(1, &[1, 6, 8]), (5, &[1, 7])]);
+        let step = find_fish(&problem).unwrap();
+        assert_eq!(
+            step.technique,
+            Technique::Fish {
+                size: 2,
+                fins: vec![15, 17],
+                sashimi: true
+            }
+        );
+        assert_eq!(
+            step.describe(problem.layout()),
+            "sashimi X-Wing in row 2 and row 6 covering column 2 and column 8 with fins r2c7 \
+             r2c9: r1c8 <> 1, r3c8 <> 1"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Locked candidates: if a digit of a house can only go into the positions the house shares
+//! with a second house, the digit is placed in the shared part of the second house as well
+//! and is removed from its remaining positions.
+//! Pointing starts from a box (cell or jigsaw region) with the digit confined to a row or
+//! column, claiming (box/line reduction) from a row or column with the digit confined to a box.
+
+use crate::layout::{House, HouseKind};
+use crate::logic::{eliminations_from, Action, Step, Technique};
+use crate::{Candidates, SudokuCandidates};
+
+pub(crate) fn is_box(house: &House) -> bool {
+    matches!(house.kind, HouseKind::Cell(_) | HouseKind::Region(_))
+}
+
+fn is_line(house: &House) -> bool {
+    matches!(house.kind, HouseKind::Row(_) | HouseKind::Column(_))
+}
+
+/// First digit of the house `confined_idx` whose positions all lie in the house `target_idx`
+/// and that can be removed from the rest of the target.
+fn locked_candidate(
+    problem: &SudokuCandidates,
+    confined_idx: usize,
+    target_idx: usize,
+    technique: Technique,
+) -> Option<Step> {
+    let houses = problem.layout().houses();
+    let (confined, target) = (&houses[confined_idx], &houses[target_idx]);
+    let shared: Vec<usize> = confined
+        .positions
+        .iter()
+        .copied()
+        .filter(|pos_idx| target.positions.contains(pos_idx))
+        .collect();
+    // a digit confined to a single position is a hidden single
+    if shared.len() < 2 {
+        return None;
+    }
+    for digit in Candidates::all(problem.layout().size()).iter() {
+        let holders: Vec<usize> = confined
+            .positions
+            .iter()
+            .copied()
+            .filter(|pos_idx| problem.get(*pos_idx).contains(digit))
+            .collect();
+        if holders.len() < 2 || holders.iter().any(|pos_idx| !shared.contains(pos_idx)) {
+            continue;
+        }
+        let others = target
+            .positions
+            .iter()
+            .copied()
+            .filter(|pos_idx| !shared.contains(pos_idx));
+        let eliminations = eliminations_from(problem, others, Candidates::single(digit));
+        if !eliminations.is_empty() {
+            return Some(Step {
+                technique,
+                positions: holders,
+                houses: vec![confined_idx, target_idx],
+                action: Action::Eliminate(eliminations),
+            });
+        }
+    }
+    None
+}
+
+/// First pointing elimination, followed by claiming ones if there is none.
+pub(crate) fn find_locked_candidates(problem: &SudokuCandidates) -> Option<Step> {
+    let layout = problem.layout();
+    let houses = layout.houses();
+    let pairs = |is_confined: fn(&House) -> bool, is_target: fn(&House) -> bool| {
+        houses
+            .iter()
+            .enumerate()
+            .filter(move |(_, house)| is_confined(house))
+            .flat_map(move |(confined_idx, house)| {
+                let mut targets: Vec<usize> = house
+                    .positions
+                    .iter()
+                    .flat_map(|pos_idx| layout.house_indices_of(*pos_idx))
+                    .filter(|target_idx| is_target(&houses[*target_idx]))
+                    .collect();
+                targets.sort_unstable();
+                targets.dedup();
+                targets
+                    .into_iter()
+                    .map(move |target_idx| (confined_idx, target_idx))
+            })
+    };
+    pairs(is_box, is_line)
+        .find_map(|(confined_idx, target_idx)| {
+            locked_candidate(problem, confined_idx, target_idx, Technique::Pointing)
+        })
+        .or_else(|| {
+            pairs(is_line, is_box).find_map(|(confined_idx, target_idx)| {
+                locked_candidate(problem, confined_idx, target_idx, Technique::Claiming)
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Elimination;
+
+    #[test]
+    fn test_pointing() {
+        // the 1 in column 1 and the pencil marks of rows 2 and 3 confine the 1 of the first
+        // cell to r1c2 and r1c3
+        let problem = crate::parse_sudoku(
+            "000000000000000000000000000000000000100000000000000000000000000000000000000000000\n\
+             candidates: 23456789 r2c2 r2c3 r3c2 r3c3",
+        )
+        .unwrap();
+        let step = find_locked_candidates(&problem).unwrap();
+        assert_eq!(step.technique, Technique::Pointing);
+        assert_eq!(step.positions, vec![1, 2]);
+        assert_eq!(
+            step.describe(problem.layout()),
+            "pointing in cell 1 and row 1 on r1c2 r1c3: r1c4 <> 1, r1c5 <> 1, r1c6 <> 1, \
+             r1c7 <> 1, r1c8 <> 1, r1c9 <> 1"
+        );
+    }
+
+    #[test]
+    fn test_claiming() {
+        // the first row is filled except for r1c1 to r1c3 and r1c9, the 1 of the row is
+        // confined to the first cell since r1c9 already has a 1 in its column
+        let problem = crate::parse_sudoku(
+            "000456780000000000000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        let step = find_locked_candidates(&problem).unwrap();
+        assert_eq!(step.technique, Technique::Claiming);
+        assert_eq!(step.positions, vec![0, 1, 2]);
+        let eliminations = match &step.action {
+            Action::Eliminate(eliminations) => eliminations.clone(),
+            action => panic!("unexpected action {:?}", action),
+        };
+        let expected: Vec<Elimination> = [9, 10, 11, 18, 19, 20]
+            .iter()
+            .map(|pos_idx| Elimination {
+                pos_idx: *pos_idx,
+                candidates: Candidates::single(1),
+            })
+            .collect();
+        assert_eq!(eliminations, expected);
+        assert_eq!(
+            step.describe(problem.layout()),
+            "claiming in row 1 and cell 1 on r1c1 r1c2 r1c3: r2c1 <> 1, r2c2 <> 1, r2c3 <> 1, \
+             r3c1 <> 1, r3c2 <> 1, r3c3 <> 1"
+        );
+    }
+}
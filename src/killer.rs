@@ -0,0 +1,167 @@
+//! Killer sudoku: cages of positions whose digits have to add up to a given sum
+//! without repeating a digit.
+
+use crate::constraint::{Constraint, Elimination};
+use crate::{Candidates, SudokuCandidates};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Cage {
+    positions: Vec<usize>,
+    sum: u32,
+    // all sets of distinct digits with the right number of elements and the right sum
+    combinations: Vec<Candidates>,
+}
+
+impl Cage {
+    pub fn new(positions: Vec<usize>, sum: u32, size: usize) -> Result<Cage, String> {
+        if positions.is_empty() {
+            return Err(format!("Cage with sum {} has no positions", sum));
+        }
+        let mut combinations = vec![];
+        find_combinations(
+            1,
+            size as u8,
+            positions.len(),
+            sum,
+            Candidates::NONE,
+            &mut combinations,
+        );
+        if combinations.is_empty() {
+            return Err(format!(
+                "No {} distinct digits add up to {}",
+                positions.len(),
+                sum
+            ));
+        }
+        Ok(Cage {
+            positions,
+            sum,
+            combinations,
+        })
+    }
+}
+
+/// Collects all sets of `count` distinct digits from `first..=last` adding up to `sum`.
+pub(crate) fn find_combinations(
+    first: u8,
+    last: u8,
+    count: usize,
+    sum: u32,
+    chosen: Candidates,
+    combinations: &mut Vec<Candidates>,
+) {
+    if count == 0 {
+        if sum == 0 {
+            combinations.push(chosen);
+        }
+        return;
+    }
+    for digit in first..=last {
+        if digit as u32 > sum {
+            break;
+        }
+        find_combinations(
+            digit + 1,
+            last,
+            count - 1,
+            sum - digit as u32,
+            chosen | Candidates::single(digit),
+            combinations,
+        );
+    }
+}
+
+impl Constraint for Cage {
+    fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+        let mut seen = Candidates::NONE;
+        let mut fixed_sum = 0;
+        let mut num_fixed = 0;
+        for pos_idx in &self.positions {
+            let cands = problem.get(*pos_idx);
+            if cands.len() != 1 {
+                continue;
+            }
+            if !(seen & cands).is_empty() {
+                return true;
+            }
+            seen = seen | cands;
+            fixed_sum += cands.first().unwrap() as u32;
+            num_fixed += 1;
+        }
+        fixed_sum > self.sum || (num_fixed == self.positions.len() && fixed_sum != self.sum)
+    }
+
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+        let cands: Vec<Candidates> = self
+            .positions
+            .iter()
+            .map(|pos_idx| problem.get(*pos_idx))
+            .collect();
+
+        // a digit stays possible if it is part of a combination that fits the cage:
+        // every position can take a digit of it and every digit of it has a position
+        let mut allowed = vec![Candidates::NONE; cands.len()];
+        for combination in &self.combinations {
+            let restricted: Vec<Candidates> =
+                cands.iter().map(|cands| *cands & *combination).collect();
+            let covered = restricted
+                .iter()
+                .fold(Candidates::NONE, |covered, cands| covered | *cands);
+            if covered != *combination || restricted.iter().any(|cands| cands.is_empty()) {
+                continue;
+            }
+            for (allowed, restricted) in allowed.iter_mut().zip(&restricted) {
+                *allowed = *allowed | *restricted;
+            }
+        }
+
+        self.positions
+            .iter()
+            .zip(cands.iter().zip(&allowed))
+            .filter(|(_, (cands, allowed))| **cands != **allowed)
+            .map(|(pos_idx, (cands, allowed))| Elimination {
+                pos_idx: *pos_idx,
+                candidates: cands.without(*allowed),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combinations() {
+        let cage = Cage::new(vec![0, 1], 3, 9).unwrap();
+        assert_eq!(
+            cage.combinations,
+            vec![Candidates::single(1) | Candidates::single(2)]
+        );
+        let cage = Cage::new(vec![0, 1, 2], 15, 9).unwrap();
+        // 1+5+9, 1+6+8, 2+4+9, 2+5+8, 2+6+7, 3+4+8, 3+5+7, 4+5+6
+        assert_eq!(cage.combinations.len(), 8);
+        assert!(Cage::new(vec![0, 1], 18, 9).is_err());
+        assert!(Cage::new(vec![0], 10, 9).is_err());
+        assert!(Cage::new(vec![], 0, 9).is_err());
+    }
+
+    #[test]
+    fn test_prune_two_cell_cage() {
+        let problem = crate::parse_sudoku(&"0".repeat(81)).unwrap();
+        // 16 in two positions is 7+9
+        let cage = Cage::new(vec![0, 1], 16, 9).unwrap();
+        let eliminations = cage.prune(&problem);
+        assert_eq!(eliminations.len(), 2);
+        for elimination in eliminations {
+            let remaining = problem
+                .get(elimination.pos_idx)
+                .without(elimination.candidates);
+            assert_eq!(remaining, Candidates::single(7) | Candidates::single(9));
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Kropki dots and XV markers: relations between the digits of two adjacent positions.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::constraint::{Constraint, Elimination};
+use crate::{Candidates, SudokuCandidates};
+
+/// Relation between the digits of two positions marked in the grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairRelation {
+    /// White dot: the digits are consecutive.
+    Consecutive,
+    /// Black dot: one digit is twice the other.
+    Double,
+    /// X (10) or V (5): the digits add up to the given sum.
+    Sum(u8),
+}
+
+impl PairRelation {
+    pub const NAMES: [&'static str; 4] = ["white", "black", "x", "v"];
+
+    /// True for two kropki dots or two XV markers. A marker exempts its pair from the
+    /// negative constraints of its kind, e.g. a white dot between 1 and 2 also allows the
+    /// double missing a black dot.
+    pub fn same_kind(self, other: PairRelation) -> bool {
+        matches!(
+            (self, other),
+            (
+                PairRelation::Consecutive | PairRelation::Double,
+                PairRelation::Consecutive | PairRelation::Double
+            ) | (PairRelation::Sum(_), PairRelation::Sum(_))
+        )
+    }
+
+    pub fn holds(self, first: u8, second: u8) -> bool {
+        match self {
+            PairRelation::Consecutive => first + 1 == second || second + 1 == first,
+            PairRelation::Double => first * 2 == second || second * 2 == first,
+            PairRelation::Sum(sum) => first + second == sum,
+        }
+    }
+}
+
+impl FromStr for PairRelation {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<PairRelation, String> {
+        match name {
+            "white" => Ok(PairRelation::Consecutive),
+            "black" => Ok(PairRelation::Double),
+            "x" => Ok(PairRelation::Sum(10)),
+            "v" => Ok(PairRelation::Sum(5)),
+            _ => Err(format!(
+                "Unknown marker {}, expected one of {:?}",
+                name,
+                PairRelation::NAMES
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PairRelation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PairRelation::Consecutive => write!(f, "white"),
+            PairRelation::Double => write!(f, "black"),
+            PairRelation::Sum(10) => write!(f, "x"),
+            PairRelation::Sum(5) => write!(f, "v"),
+            PairRelation::Sum(sum) => write!(f, "sum {}", sum),
+        }
+    }
+}
+
+/// Candidates of `cands` for which `other` contains a digit with `relation` equal to `expected`.
+/// Adjacent positions share a row or column, so the digits are always different.
+fn supported(
+    relation: PairRelation,
+    expected: bool,
+    cands: Candidates,
+    other: Candidates,
+) -> Candidates {
+    cands
+        .iter()
+        .filter(|digit| {
+            other.iter().any(|other_digit| {
+                other_digit != *digit && relation.holds(*digit, other_digit) == expected
+            })
+        })
+        .fold(Candidates::NONE, |supported, digit| {
+            supported | Candidates::single(digit)
+        })
+}
+
+/// Eliminations for a pair of positions whose digits have to satisfy the relation
+/// (or must not, if `expected` is false).
+fn prune_pair(
+    relation: PairRelation,
+    expected: bool,
+    pair: [usize; 2],
+    problem: &SudokuCandidates,
+    eliminations: &mut Vec<Elimination>,
+) {
+    let cands = [problem.get(pair[0]), problem.get(pair[1])];
+    for (idx, pos_idx) in pair.iter().enumerate() {
+        let remaining = supported(relation, expected, cands[idx], cands[1 - idx]);
+        if remaining != cands[idx] {
+            eliminations.push(Elimination {
+                pos_idx: *pos_idx,
+                candidates: cands[idx].without(remaining),
+            });
+        }
+    }
+}
+
+fn violates(
+    relation: PairRelation,
+    expected: bool,
+    pair: [usize; 2],
+    problem: &SudokuCandidates,
+) -> bool {
+    let (first, second) = (problem.get(pair[0]), problem.get(pair[1]));
+    first.len() == 1
+        && second.len() == 1
+        && relation.holds(first.first().unwrap(), second.first().unwrap()) != expected
+}
+
+/// A marker between two adjacent positions.
+#[derive(Clone, Debug)]
+pub(crate) struct PairMarker {
+    relation: PairRelation,
+    positions: Vec<usize>,
+}
+
+impl PairMarker {
+    pub fn new(relation: PairRelation, pair: [usize; 2]) -> PairMarker {
+        PairMarker {
+            relation,
+            positions: pair.to_vec(),
+        }
+    }
+
+    fn pair(&self) -> [usize; 2] {
+        [self.positions[0], self.positions[1]]
+    }
+}
+
+impl Constraint for PairMarker {
+    fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+        violates(self.relation, true, self.pair(), problem)
+    }
+
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+        let mut eliminations = vec![];
+        prune_pair(self.relation, true, self.pair(), problem, &mut eliminations);
+        eliminations
+    }
+}
+
+/// Negative constraint: adjacent positions without a marker of the relation must not
+/// satisfy it, e.g. no consecutive digits unless there is a white dot between them.
+#[derive(Clone, Debug)]
+pub(crate) struct UnmarkedPairs {
+    relation: PairRelation,
+    pairs: Vec<[usize; 2]>,
+    positions: Vec<usize>,
+}
+
+impl UnmarkedPairs {
+    /// `marked` are the pairs with a marker of the same kind, all other `adjacent` pairs
+    /// are constrained.
+    pub fn new(
+        relation: PairRelation,
+        adjacent: Vec<[usize; 2]>,
+        marked: &[[usize; 2]],
+    ) -> UnmarkedPairs {
+        let pairs: Vec<[usize; 2]> = adjacent
+            .into_iter()
+            .filter(|pair| {
+                !marked
+                    .iter()
+                    .any(|marked| marked == pair || (marked[0] == pair[1] && marked[1] == pair[0]))
+            })
+            .collect();
+        let mut positions: Vec<usize> = pairs.iter().flatten().copied().collect();
+        positions.sort_unstable();
+        positions.dedup();
+        UnmarkedPairs {
+            relation,
+            pairs,
+            positions,
+        }
+    }
+}
+
+impl Constraint for UnmarkedPairs {
+    fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+        self.pairs
+            .iter()
+            .any(|pair| violates(self.relation, false, *pair, problem))
+    }
+
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+        let mut eliminations = vec![];
+        for pair in &self.pairs {
+            prune_pair(self.relation, false, *pair, problem, &mut eliminations);
+        }
+        eliminations
+    }
+}
+
+/// All pairs of horizontally or vertically adjacent positions of a `size` x `size` grid.
+pub(crate) fn adjacent_pairs(size: usize) -> Vec<[usize; 2]> {
+    let mut pairs = vec![];
+    for pos_idx in 0..size * size {
+        if pos_idx % size + 1 < size {
+            pairs.push([pos_idx, pos_idx + 1]);
+        }
+        if pos_idx + size < size * size {
+            pairs.push([pos_idx, pos_idx + size]);
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraint::remaining;
+
+    #[test]
+    fn test_relations() {
+        assert!(PairRelation::Consecutive.holds(4, 3));
+        assert!(!PairRelation::Consecutive.holds(4, 2));
+        assert!(PairRelation::Double.holds(3, 6));
+        assert!(PairRelation::Double.holds(8, 4));
+        assert!(!PairRelation::Double.holds(3, 5));
+        assert_eq!("x".parse(), Ok(PairRelation::Sum(10)));
+        assert!("y".parse::<PairRelation>().is_err());
+        assert!(PairRelation::Consecutive.same_kind(PairRelation::Double));
+        assert!(PairRelation::Sum(5).same_kind(PairRelation::Sum(10)));
+        assert!(!PairRelation::Sum(5).same_kind(PairRelation::Double));
+    }
+
+    #[test]
+    fn test_prune_markers() {
+        // 1 and 3 in the first two positions of the second row
+        let problem = crate::parse_sudoku(&("0".repeat(9) + "13" + &"0".repeat(70))).unwrap();
+        let black = PairMarker::new(PairRelation::Double, [0, 1]);
+        // 1 and 3 are peers, which rules out 6 as well, 5, 7 and 9 have no double or half
+        assert_eq!(
+            remaining(&problem, &black),
+            vec![vec![2, 4, 8], vec![2, 4, 8]]
+        );
+        let x = PairMarker::new(PairRelation::Sum(10), [0, 1]);
+        assert_eq!(
+            remaining(&problem, &x),
+            vec![vec![2, 4, 6, 8], vec![2, 4, 6, 8]]
+        );
+    }
+
+    #[test]
+    fn test_prune_unmarked_pairs() {
+        // 5 in the top left corner, the white dot is between it and its right neighbor
+        let problem = crate::parse_sudoku(&("5".to_owned() + &"0".repeat(80))).unwrap();
+        let unmarked = UnmarkedPairs::new(PairRelation::Consecutive, adjacent_pairs(9), &[[1, 0]]);
+        assert_eq!(unmarked.pairs.len(), 2 * 9 * 8 - 1);
+        let eliminations = unmarked.prune(&problem);
+        assert_eq!(
+            eliminations,
+            vec![Elimination {
+                pos_idx: 9,
+                candidates: Candidates::single(4) | Candidates::single(6),
+            }]
+        );
+    }
+}
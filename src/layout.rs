@@ -0,0 +1,996 @@
+//! Dimensions of a sudoku grid and the groups of positions ("houses") that must not contain
+//! a digit twice.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::arrow::Arrow;
+use crate::chess::{move_pairs, ChessPiece};
+use crate::constraint::Constraint;
+use crate::killer::Cage;
+use crate::kropki::{adjacent_pairs, PairMarker, PairRelation, UnmarkedPairs};
+use crate::restriction::DigitRestriction;
+use crate::sandwich::Sandwich;
+use crate::thermo::Thermometer;
+use crate::Candidates;
+
+/// Largest supported number of digits, limited by the bitmask used for the candidates.
+pub const MAX_SIZE: usize = 25;
+
+/// What a house corresponds to in the grid, mainly used for reporting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HouseKind {
+    Row(usize),
+    Column(usize),
+    Cell(usize),
+    /// Irregular region replacing the cells of a jigsaw sudoku.
+    Region(usize),
+    /// 0 is the main diagonal from the top left, 1 the anti-diagonal from the top right.
+    Diagonal(usize),
+    /// Additional cell-sized window of a hyper sudoku, numbered in row major order.
+    Window(usize),
+    /// Arbitrary additional house declared for a single problem.
+    Extra(usize),
+}
+
+impl fmt::Display for HouseKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HouseKind::Row(idx) => write!(f, "row {}", idx + 1),
+            HouseKind::Column(idx) => write!(f, "column {}", idx + 1),
+            HouseKind::Cell(idx) => write!(f, "cell {}", idx + 1),
+            HouseKind::Region(idx) => write!(f, "region {}", idx + 1),
+            HouseKind::Diagonal(0) => write!(f, "diagonal"),
+            HouseKind::Diagonal(_) => write!(f, "anti-diagonal"),
+            HouseKind::Window(idx) => write!(f, "window {}", idx + 1),
+            HouseKind::Extra(idx) => write!(f, "extra house {}", idx + 1),
+        }
+    }
+}
+
+/// Group of positions that has to contain each digit exactly once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct House {
+    pub kind: HouseKind,
+    pub positions: Vec<usize>,
+    /// Index of the grid the house belongs to if the layout consists of several grids,
+    /// e.g. for samurai sudokus.
+    pub grid: Option<usize>,
+}
+
+impl fmt::Display for House {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.grid {
+            Some(grid_idx) => write!(f, "{} of grid {}", self.kind, grid_idx + 1),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// Optional rules in addition to the regular row, column and cell constraints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Variant {
+    /// Both main diagonals have to contain every digit once (Sudoku-X).
+    pub diagonal: bool,
+    /// Positions a chess knight's move apart must not contain the same digit.
+    pub anti_knight: bool,
+    /// Positions a chess king's move apart (i.e. touching diagonally) must not contain the same digit.
+    pub anti_king: bool,
+    /// Windows of cell size between the cells have to contain every digit once
+    /// (Hyper sudoku or Windoku).
+    pub hyper: bool,
+}
+
+impl Variant {
+    pub const NAMES: [&'static str; 4] = ["diagonal", "anti-knight", "anti-king", "hyper"];
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    /// Parses a comma separated list of rule names, e.g. "diagonal,anti-knight".
+    /// An empty string is the regular sudoku.
+    fn from_str(names: &str) -> Result<Variant, String> {
+        let mut variant = Variant::default();
+        for name in names.split(',').map(|name| name.trim()) {
+            match name {
+                "" => {}
+                "diagonal" => variant.diagonal = true,
+                "anti-knight" => variant.anti_knight = true,
+                "anti-king" => variant.anti_king = true,
+                "hyper" => variant.hyper = true,
+                _ => {
+                    return Err(format!(
+                        "Unknown variant {}, expected one of {:?}",
+                        name,
+                        Variant::NAMES
+                    ))
+                }
+            }
+        }
+        Ok(variant)
+    }
+}
+
+/// A `size` x `size` grid divided into cells of `cell_rows` x `cell_cols` positions,
+/// e.g. 9x9 with 3x3 cells or 6x6 with 2x3 cells. Positions are indexed in row major order.
+/// A layout can also consist of several overlapping grids on a larger board (samurai sudoku),
+/// positions shared by two grids are the same position in both of them.
+#[derive(Clone, Debug)]
+pub struct Layout {
+    cell_rows: usize,
+    cell_cols: usize,
+    size: usize,
+    variant: Variant,
+    // positions of each grid in row major order
+    grids: Vec<Vec<usize>>,
+    // number of columns of the board holding the grids
+    board_cols: usize,
+    // position of each field of the board in row major order, None outside of the grids
+    board: Vec<Option<usize>>,
+    // field of the board of each position
+    fields: Vec<usize>,
+    houses: Vec<House>,
+    // indices into houses for each position
+    houses_of_pos: Vec<Vec<usize>>,
+    // positions that must not hold the same digit as each position
+    peers: Vec<Vec<usize>>,
+    // groups of positions with distinct digits that are not houses, e.g. killer cages
+    peer_groups: Vec<Vec<usize>>,
+    constraints: Vec<Arc<dyn Constraint>>,
+    // pairs of positions with a kropki dot or XV marker
+    markers: Vec<(PairRelation, [usize; 2])>,
+    // relations that unmarked adjacent pairs must not satisfy, with the index into constraints
+    negative_relations: Vec<(PairRelation, usize)>,
+}
+
+impl Layout {
+    /// Panics if the resulting grid is larger than `MAX_SIZE`.
+    pub fn new(cell_rows: usize, cell_cols: usize) -> Layout {
+        Layout::with_grids(cell_rows, cell_cols, &[(0, 0)])
+    }
+
+    /// Samurai sudoku: four grids around a center grid, which shares one corner cell with each
+    /// of them, e.g. five 9x9 grids on a 21x21 board. The grids are numbered top left,
+    /// top right, center, bottom left and bottom right.
+    /// Panics if the grids are larger than `MAX_SIZE`.
+    pub fn samurai(cell_rows: usize, cell_cols: usize) -> Layout {
+        let size = cell_rows * cell_cols;
+        // the center grid starts at the last cell of the top left grid
+        let (row_offset, col_offset) = (size - cell_rows, size - cell_cols);
+        Layout::with_grids(
+            cell_rows,
+            cell_cols,
+            &[
+                (0, 0),
+                (0, 2 * col_offset),
+                (row_offset, col_offset),
+                (2 * row_offset, 0),
+                (2 * row_offset, 2 * col_offset),
+            ],
+        )
+    }
+
+    /// Grids starting at the given row and column of the board, each with its own rows,
+    /// columns and cells. Cells shared by several grids are only added once.
+    fn with_grids(cell_rows: usize, cell_cols: usize, origins: &[(usize, usize)]) -> Layout {
+        let size = cell_rows * cell_cols;
+        assert!(
+            size > 1 && size <= MAX_SIZE,
+            "Unsupported sudoku size {}",
+            size
+        );
+
+        let board_rows = origins.iter().map(|(row, _)| row + size).max().unwrap_or(0);
+        let board_cols = origins.iter().map(|(_, col)| col + size).max().unwrap_or(0);
+        let mut board = vec![None; board_rows * board_cols];
+        let mut fields = vec![];
+        let mut grids = vec![];
+        for &(first_row, first_col) in origins {
+            let mut grid = vec![];
+            for row_idx in first_row..first_row + size {
+                for col_idx in first_col..first_col + size {
+                    let field = row_idx * board_cols + col_idx;
+                    let pos_idx = *board[field].get_or_insert_with(|| {
+                        fields.push(field);
+                        fields.len() - 1
+                    });
+                    grid.push(pos_idx);
+                }
+            }
+            grids.push(grid);
+        }
+
+        // rows, columns and cells in the coordinates of a single grid
+        let mut grid_houses: Vec<(HouseKind, Vec<usize>)> = vec![];
+        for row_idx in 0..size {
+            grid_houses.push((
+                HouseKind::Row(row_idx),
+                (0..size).map(|col_idx| row_idx * size + col_idx).collect(),
+            ));
+        }
+        for col_idx in 0..size {
+            grid_houses.push((
+                HouseKind::Column(col_idx),
+                (0..size).map(|row_idx| row_idx * size + col_idx).collect(),
+            ));
+        }
+        // cells are numbered in row major order as well
+        for cell_idx in 0..size {
+            let first_row = cell_idx / cell_rows * cell_rows;
+            let first_col = cell_idx % cell_rows * cell_cols;
+            grid_houses.push((
+                HouseKind::Cell(cell_idx),
+                (0..size)
+                    .map(|i| (first_row + i / cell_cols) * size + first_col + i % cell_cols)
+                    .collect(),
+            ));
+        }
+
+        let mut houses: Vec<House> = vec![];
+        for (grid_idx, grid) in grids.iter().enumerate() {
+            for (kind, grid_positions) in &grid_houses {
+                let positions: Vec<usize> = grid_positions.iter().map(|idx| grid[*idx]).collect();
+                if houses.iter().all(|house| house.positions != positions) {
+                    houses.push(House {
+                        kind: *kind,
+                        positions,
+                        grid: if grids.len() > 1 {
+                            Some(grid_idx)
+                        } else {
+                            None
+                        },
+                    });
+                }
+            }
+        }
+
+        let mut layout = Layout {
+            cell_rows,
+            cell_cols,
+            size,
+            variant: Variant::default(),
+            grids,
+            board_cols,
+            board,
+            fields,
+            houses: vec![],
+            houses_of_pos: vec![],
+            peers: vec![],
+            peer_groups: vec![],
+            constraints: vec![],
+            markers: vec![],
+            negative_relations: vec![],
+        };
+        layout.set_houses(houses);
+        layout
+    }
+
+    /// Adds the houses and constraints required by the variant to every grid.
+    /// Rules that are already part of the layout are not added again.
+    pub fn with_variant(mut self, variant: Variant) -> Layout {
+        let size = self.size;
+        let mut houses = self.houses.clone();
+        // houses in the coordinates of a single grid, added for every grid
+        let mut grid_houses: Vec<(HouseKind, Vec<usize>)> = vec![];
+        if variant.diagonal && !self.variant.diagonal {
+            grid_houses.push((
+                HouseKind::Diagonal(0),
+                (0..size).map(|idx| idx * size + idx).collect(),
+            ));
+            grid_houses.push((
+                HouseKind::Diagonal(1),
+                (0..size).map(|idx| idx * size + size - 1 - idx).collect(),
+            ));
+        }
+        if variant.hyper && !self.variant.hyper {
+            // one position apart from the border and from each other, e.g. starting in
+            // row and column 2 and 6 for 9x9
+            let (cell_rows, cell_cols) = (self.cell_rows, self.cell_cols);
+            let window_starts = |cell_len: usize, num_windows: usize| {
+                (0..num_windows).map(move |idx| 1 + idx * (cell_len + 1))
+            };
+            let first_window = grid_houses.len();
+            for first_row in window_starts(cell_rows, cell_cols - 1) {
+                for first_col in window_starts(cell_cols, cell_rows - 1) {
+                    grid_houses.push((
+                        HouseKind::Window(grid_houses.len() - first_window),
+                        (0..size)
+                            .map(|i| (first_row + i / cell_cols) * size + first_col + i % cell_cols)
+                            .collect(),
+                    ));
+                }
+            }
+        }
+        for (grid_idx, grid) in self.grids.iter().enumerate() {
+            houses.extend(grid_houses.iter().map(|(kind, grid_positions)| House {
+                kind: *kind,
+                positions: grid_positions.iter().map(|idx| grid[*idx]).collect(),
+                grid: self.grid_label(grid_idx),
+            }));
+        }
+        if variant.anti_knight && !self.variant.anti_knight {
+            self.add_anti_move(ChessPiece::Knight);
+        }
+        if variant.anti_king && !self.variant.anti_king {
+            self.add_anti_move(ChessPiece::King);
+        }
+        self.variant = Variant {
+            diagonal: self.variant.diagonal || variant.diagonal,
+            anti_knight: self.variant.anti_knight || variant.anti_knight,
+            anti_king: self.variant.anti_king || variant.anti_king,
+            hyper: self.variant.hyper || variant.hyper,
+        };
+        self.set_houses(houses);
+        self
+    }
+
+    /// Rules of the layout in addition to rows, columns and cells.
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    fn add_anti_move(&mut self, piece: ChessPiece) {
+        for pair in self.grid_pairs(&move_pairs(piece, self.size)) {
+            self.add_peer_group(pair.to_vec());
+        }
+    }
+
+    /// Pairs of positions given in the coordinates of a single grid for every grid,
+    /// pairs in the overlap of two grids are only returned once.
+    fn grid_pairs(&self, pairs: &[[usize; 2]]) -> Vec<[usize; 2]> {
+        let mut grid_pairs: Vec<[usize; 2]> = self
+            .grids
+            .iter()
+            .flat_map(|grid| pairs.iter().map(move |pair| [grid[pair[0]], grid[pair[1]]]))
+            .map(|[first, second]| [first.min(second), first.max(second)])
+            .collect();
+        if self.grids.len() > 1 {
+            grid_pairs.sort_unstable();
+            grid_pairs.dedup();
+        }
+        grid_pairs
+    }
+
+    /// Index of the grid for houses, None unless there are several grids.
+    fn grid_label(&self, grid_idx: usize) -> Option<usize> {
+        if self.grids.len() > 1 {
+            Some(grid_idx)
+        } else {
+            None
+        }
+    }
+
+    /// Adds a killer cage: the digits of the positions have to add up to `sum` without
+    /// repeating a digit. Fails if the positions are invalid or no such digits exist.
+    pub fn with_cage(mut self, positions: Vec<usize>, sum: u32) -> Result<Layout, String> {
+        self.check_positions(&positions)?;
+        let cage = Cage::new(positions.clone(), sum, self.size)?;
+        self.add_peer_group(positions);
+        self.constraints.push(Arc::new(cage));
+        Ok(self)
+    }
+
+    /// Adds a thermometer: the digits of the positions have to strictly increase,
+    /// starting at the bulb given first.
+    pub fn with_thermometer(mut self, positions: Vec<usize>) -> Result<Layout, String> {
+        self.check_positions(&positions)?;
+        let thermometer = Thermometer::new(positions.clone(), self.size)?;
+        self.add_peer_group(positions);
+        self.constraints.push(Arc::new(thermometer));
+        Ok(self)
+    }
+
+    /// Adds an arrow: the digits of the shaft positions have to add up to the number in the
+    /// circle, which is read from several positions like a multi-digit number for a pill.
+    pub fn with_arrow(mut self, circle: Vec<usize>, shaft: Vec<usize>) -> Result<Layout, String> {
+        self.check_positions(&[circle.as_slice(), shaft.as_slice()].concat())?;
+        let arrow = Arrow::new(circle, shaft)?;
+        self.constraints.push(Arc::new(arrow));
+        Ok(self)
+    }
+
+    /// Adds a sandwich clue for a row or column: the digits between the smallest and the
+    /// largest digit have to add up to `sum`. Only supported for a single grid.
+    pub fn with_sandwich(mut self, line: HouseKind, sum: u32) -> Result<Layout, String> {
+        self.check_single_grid("Sandwich clues")?;
+        let size = self.size;
+        let positions = match line {
+            HouseKind::Row(row_idx) if row_idx < size => {
+                (0..size).map(|col_idx| row_idx * size + col_idx).collect()
+            }
+            HouseKind::Column(col_idx) if col_idx < size => {
+                (0..size).map(|row_idx| row_idx * size + col_idx).collect()
+            }
+            _ => return Err(format!("No sandwich clues for {}", line)),
+        };
+        let sandwich = Sandwich::new(positions, sum)?;
+        self.constraints.push(Arc::new(sandwich));
+        Ok(self)
+    }
+
+    /// Adds a user-defined rule, which the solvers consult like the built-in ones.
+    /// Fails if the constraint refers to positions outside of the grid.
+    pub fn with_constraint(
+        mut self,
+        constraint: impl Constraint + 'static,
+    ) -> Result<Layout, String> {
+        self.check_positions(constraint.positions())?;
+        self.constraints.push(Arc::new(constraint));
+        Ok(self)
+    }
+
+    /// Adds a kropki dot or XV marker between two orthogonally adjacent positions.
+    pub fn with_marker(
+        mut self,
+        relation: PairRelation,
+        first: usize,
+        second: usize,
+    ) -> Result<Layout, String> {
+        self.check_adjacent(first, second)?;
+        self.markers.push((relation, [first, second]));
+        self.constraints
+            .push(Arc::new(PairMarker::new(relation, [first, second])));
+        // the negative constraints must not apply to the new marker
+        for (negative, constraint_idx) in self.negative_relations.clone() {
+            if negative.same_kind(relation) {
+                self.constraints[constraint_idx] = self.unmarked_pairs(negative);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Restricts the positions to the allowed digits, e.g. to the even digits for the gray
+    /// squares of an even/odd sudoku or to the pencil marks of a position.
+    pub fn with_restriction(
+        mut self,
+        positions: Vec<usize>,
+        allowed: Candidates,
+    ) -> Result<Layout, String> {
+        self.check_positions(&positions)?;
+        let restriction = DigitRestriction::new(positions, allowed & Candidates::all(self.size))?;
+        self.constraints.push(Arc::new(restriction));
+        Ok(self)
+    }
+
+    /// Adds an inequality sign between two orthogonally adjacent positions: the digit of
+    /// `greater` has to be larger than the one of `smaller`.
+    pub fn with_inequality(mut self, greater: usize, smaller: usize) -> Result<Layout, String> {
+        self.check_adjacent(greater, smaller)?;
+        // an inequality is a thermometer of two positions, starting at the smaller one
+        let thermometer = Thermometer::new(vec![smaller, greater], self.size)?;
+        self.constraints.push(Arc::new(thermometer));
+        Ok(self)
+    }
+
+    /// Adds the negative constraint for the relation: adjacent positions without a marker of
+    /// the same kind must not satisfy it, e.g. no consecutive digits next to each other
+    /// unless there is a kropki dot between them.
+    pub fn with_negative_constraint(mut self, relation: PairRelation) -> Layout {
+        if self
+            .negative_relations
+            .iter()
+            .all(|(negative, _)| *negative != relation)
+        {
+            self.negative_relations
+                .push((relation, self.constraints.len()));
+            let unmarked_pairs = self.unmarked_pairs(relation);
+            self.constraints.push(unmarked_pairs);
+        }
+        self
+    }
+
+    fn unmarked_pairs(&self, relation: PairRelation) -> Arc<dyn Constraint> {
+        let marked: Vec<[usize; 2]> = self
+            .markers
+            .iter()
+            .filter(|(marker, _)| marker.same_kind(relation))
+            .map(|(_, pair)| *pair)
+            .collect();
+        let adjacent = self.grid_pairs(&adjacent_pairs(self.size));
+        Arc::new(UnmarkedPairs::new(relation, adjacent, &marked))
+    }
+
+    /// Adds a house: the positions have to contain every digit exactly once.
+    pub fn with_house(mut self, positions: Vec<usize>) -> Result<Layout, String> {
+        self.check_positions(&positions)?;
+        if positions.len() != self.size {
+            return Err(format!(
+                "House needs {} positions, got {}",
+                self.size,
+                positions.len()
+            ));
+        }
+        let mut houses = self.houses.clone();
+        let extra_idx = houses
+            .iter()
+            .filter(|house| matches!(house.kind, HouseKind::Extra(_)))
+            .count();
+        houses.push(House {
+            kind: HouseKind::Extra(extra_idx),
+            positions,
+            grid: None,
+        });
+        self.set_houses(houses);
+        Ok(self)
+    }
+
+    /// Replaces the cells by irregular regions (jigsaw sudoku), given as the region index of
+    /// each position. Fails unless the regions partition the grid into `size` regions of
+    /// `size` positions each. Only supported for a single grid.
+    pub fn with_regions(mut self, region_of_pos: &[usize]) -> Result<Layout, String> {
+        self.check_single_grid("Irregular regions")?;
+        if region_of_pos.len() != self.num_positions() {
+            return Err(format!(
+                "Expected regions for {} positions, got {}",
+                self.num_positions(),
+                region_of_pos.len()
+            ));
+        }
+        let mut regions = vec![vec![]; self.size];
+        for (pos_idx, region_idx) in region_of_pos.iter().enumerate() {
+            match regions.get_mut(*region_idx) {
+                Some(region) => region.push(pos_idx),
+                None => return Err(format!("Expected {} regions", self.size)),
+            }
+        }
+        if let Some(region_idx) = regions.iter().position(|region| region.len() != self.size) {
+            return Err(format!(
+                "Region {} has {} positions instead of {}",
+                region_idx + 1,
+                regions[region_idx].len(),
+                self.size
+            ));
+        }
+
+        let mut houses: Vec<House> = self
+            .houses
+            .iter()
+            .filter(|house| !matches!(house.kind, HouseKind::Cell(_) | HouseKind::Region(_)))
+            .cloned()
+            .collect();
+        houses.extend(
+            regions
+                .into_iter()
+                .enumerate()
+                .map(|(region_idx, positions)| House {
+                    kind: HouseKind::Region(region_idx),
+                    positions,
+                    grid: None,
+                }),
+        );
+        self.set_houses(houses);
+        Ok(self)
+    }
+
+    fn check_positions(&self, positions: &[usize]) -> Result<(), String> {
+        for (idx, pos_idx) in positions.iter().enumerate() {
+            if *pos_idx >= self.num_positions() {
+                return Err(format!("Position {} is outside of the grid", pos_idx));
+            }
+            if positions[..idx].contains(pos_idx) {
+                let (row_idx, col_idx) = self.row_col(*pos_idx);
+                return Err(format!(
+                    "Position r{}c{} is given twice",
+                    row_idx + 1,
+                    col_idx + 1
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_adjacent(&self, first: usize, second: usize) -> Result<(), String> {
+        self.check_positions(&[first, second])?;
+        let ((first_row, first_col), (second_row, second_col)) =
+            (self.row_col(first), self.row_col(second));
+        if first_row.abs_diff(second_row) + first_col.abs_diff(second_col) != 1 {
+            return Err(format!(
+                "Positions r{}c{} and r{}c{} are not adjacent",
+                first_row + 1,
+                first_col + 1,
+                second_row + 1,
+                second_col + 1
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_single_grid(&self, rule: &str) -> Result<(), String> {
+        if self.grids.len() > 1 {
+            return Err(format!("{} are only supported for a single grid", rule));
+        }
+        Ok(())
+    }
+
+    /// Makes all positions of the group peers of each other.
+    fn add_peer_group(&mut self, positions: Vec<usize>) {
+        add_peers(&mut self.peers, &positions);
+        self.peer_groups.push(positions);
+    }
+
+    /// Layout with the most square cells for the given size, e.g. 3x4 cells for 12x12.
+    /// Returns None if the size is unsupported or prime, i.e. has no proper cells.
+    pub fn for_size(size: usize) -> Option<Layout> {
+        if size > MAX_SIZE {
+            return None;
+        }
+        let cell_rows = (2..size)
+            .take_while(|cell_rows| cell_rows * cell_rows <= size)
+            .filter(|cell_rows| size.is_multiple_of(*cell_rows))
+            .last()?;
+        Some(Layout::new(cell_rows, size / cell_rows))
+    }
+
+    /// Layout for a square grid with the given total number of positions, e.g. 81 for 9x9.
+    pub fn for_num_positions(num_positions: usize) -> Option<Layout> {
+        let size = (num_positions as f64).sqrt().round() as usize;
+        if size * size != num_positions {
+            return None;
+        }
+        Layout::for_size(size)
+    }
+
+    /// Layout for a problem with the given number of fields in the text format: a square grid,
+    /// or the five 9x9 grids of a samurai sudoku one after another (405 fields).
+    pub fn for_num_fields(num_fields: usize) -> Option<Layout> {
+        match grid_size(num_fields) {
+            Some((size, 1)) => Layout::for_size(size),
+            Some((size, _)) => Layout::for_size(size)
+                .map(|layout| Layout::samurai(layout.cell_rows, layout.cell_cols)),
+            None => None,
+        }
+    }
+
+    fn set_houses(&mut self, houses: Vec<House>) {
+        let num_positions = self.num_positions();
+        let mut houses_of_pos = vec![vec![]; num_positions];
+        let mut peers = vec![vec![]; num_positions];
+        for (house_idx, house) in houses.iter().enumerate() {
+            for pos_idx in &house.positions {
+                houses_of_pos[*pos_idx].push(house_idx);
+            }
+            add_peers(&mut peers, &house.positions);
+        }
+        for group in &self.peer_groups {
+            add_peers(&mut peers, group);
+        }
+        self.houses = houses;
+        self.houses_of_pos = houses_of_pos;
+        self.peers = peers;
+    }
+
+    /// True if some positions must hold different digits without sharing a house.
+    pub(crate) fn has_peer_groups(&self) -> bool {
+        !self.peer_groups.is_empty()
+    }
+
+    /// Rules in addition to the houses, e.g. killer cages or the ones added by
+    /// `with_constraint`.
+    pub fn constraints(&self) -> &[Arc<dyn Constraint>] {
+        &self.constraints
+    }
+
+    /// All rules of the layout: the houses, followed by the additional constraints.
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Constraint> {
+        let houses = self.houses.iter().map(|house| house as &dyn Constraint);
+        houses.chain(
+            self.constraints
+                .iter()
+                .map(|constraint| constraint.as_ref()),
+        )
+    }
+
+    /// Number of digits, as well as number of rows and columns of each grid.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn cell_rows(&self) -> usize {
+        self.cell_rows
+    }
+
+    pub fn cell_cols(&self) -> usize {
+        self.cell_cols
+    }
+
+    /// Number of distinct positions, positions shared by several grids are counted once.
+    pub fn num_positions(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Positions of each grid in row major order, a single grid unless this is a samurai sudoku.
+    pub fn grids(&self) -> &[Vec<usize>] {
+        &self.grids
+    }
+
+    /// Number of rows and columns of the board, larger than `size` for several grids.
+    pub fn board_size(&self) -> (usize, usize) {
+        (self.board.len() / self.board_cols, self.board_cols)
+    }
+
+    /// Positions in the order of the text format, i.e. the positions of all grids one after
+    /// another. Shared positions appear once for each of their grids.
+    pub fn text_positions(&self) -> impl Iterator<Item = usize> + '_ {
+        self.grids.iter().flatten().copied()
+    }
+
+    pub fn houses(&self) -> &[House] {
+        &self.houses
+    }
+
+    /// Houses containing the position.
+    pub fn houses_of(&self, pos_idx: usize) -> impl Iterator<Item = &House> {
+        self.houses_of_pos[pos_idx]
+            .iter()
+            .map(move |house_idx| &self.houses[*house_idx])
+    }
+
+    /// Indices into `houses` of the houses containing the position.
+    pub fn house_indices_of(&self, pos_idx: usize) -> impl Iterator<Item = usize> + '_ {
+        self.houses_of_pos[pos_idx].iter().copied()
+    }
+
+    /// Positions that must not hold the same digit as the position, i.e. the ones sharing a house
+    /// or a group like a killer cage with it, excluding the position itself.
+    pub fn peers(&self, pos_idx: usize) -> &[usize] {
+        &self.peers[pos_idx]
+    }
+
+    /// Position at the row and column of the board, None outside of the grids.
+    pub fn pos_idx(&self, row_idx: usize, col_idx: usize) -> Option<usize> {
+        if col_idx >= self.board_cols {
+            return None;
+        }
+        *self.board.get(row_idx * self.board_cols + col_idx)?
+    }
+
+    /// Row and column of the position on the board.
+    pub fn row_col(&self, pos_idx: usize) -> (usize, usize) {
+        let field = self.fields[pos_idx];
+        (field / self.board_cols, field % self.board_cols)
+    }
+
+    /// Name of the position in messages, e.g. "r3c5" with rows and columns counted from 1.
+    pub fn position_name(&self, pos_idx: usize) -> String {
+        let (row_idx, col_idx) = self.row_col(pos_idx);
+        format!("r{}c{}", row_idx + 1, col_idx + 1)
+    }
+
+    /// Character used for a digit in the compact text format: 1-9 followed by A-Z.
+    pub fn symbol(digit: u8) -> char {
+        std::char::from_digit(digit as u32, 36)
+            .map(|c| c.to_ascii_uppercase())
+            .unwrap_or('?')
+    }
+
+    /// Digit for a symbol of the compact text format, None for unknown symbols or digits
+    /// larger than the grid size.
+    pub fn digit(&self, symbol: char) -> Option<u8> {
+        symbol
+            .to_digit(36)
+            .filter(|digit| *digit >= 1 && *digit as usize <= self.size)
+            .map(|digit| digit as u8)
+    }
+}
+
+/// Number of fields of a samurai sudoku in the text format: five 9x9 grids one after another.
+const SAMURAI_FIELDS: usize = 5 * 81;
+
+/// Size of the grids and number of grids for the number of fields in the text format,
+/// None if neither a square grid nor the five 9x9 grids of a samurai sudoku fit.
+pub(crate) fn grid_size(num_fields: usize) -> Option<(usize, usize)> {
+    if num_fields == SAMURAI_FIELDS {
+        return Some((9, 5));
+    }
+    let size = (num_fields as f64).sqrt().round() as usize;
+    if size * size == num_fields {
+        Some((size, 1))
+    } else {
+        None
+    }
+}
+
+fn add_peers(peers: &mut [Vec<usize>], group: &[usize]) {
+    for pos_idx in group {
+        for peer_idx in group {
+            if peer_idx != pos_idx && !peers[*pos_idx].contains(peer_idx) {
+                peers[*pos_idx].push(*peer_idx);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_layouts() {
+        for (size, cell_rows, cell_cols) in [
+            (4, 2, 2),
+            (6, 2, 3),
+            (9, 3, 3),
+            (12, 3, 4),
+            (16, 4, 4),
+            (25, 5, 5),
+        ] {
+            let layout = Layout::for_size(size).unwrap();
+            assert_eq!(
+                (layout.cell_rows(), layout.cell_cols()),
+                (cell_rows, cell_cols)
+            );
+            assert_eq!(layout.houses().len(), 3 * size);
+            // row, column and cell peers without the intersections
+            let num_peers = 3 * (size - 1) - (cell_rows - 1) - (cell_cols - 1);
+            assert!((0..layout.num_positions()).all(|pos| layout.peers(pos).len() == num_peers));
+        }
+        assert!(Layout::for_size(7).is_none());
+        assert!(Layout::for_num_positions(80).is_none());
+    }
+
+    #[test]
+    fn test_rectangular_cells() {
+        let layout = Layout::new(2, 3);
+        let cell = &layout.houses()[2 * 6 + 1];
+        assert_eq!(cell.kind, HouseKind::Cell(1));
+        assert_eq!(cell.positions, vec![3, 4, 5, 9, 10, 11]);
+        let cell = &layout.houses()[2 * 6 + 2];
+        assert_eq!(cell.positions, vec![12, 13, 14, 18, 19, 20]);
+    }
+
+    #[test]
+    fn test_diagonal_variant() {
+        let variant: Variant = "diagonal".parse().unwrap();
+        let layout = Layout::for_size(9).unwrap().with_variant(variant);
+        assert_eq!(layout.houses().len(), 29);
+        // the center is on both diagonals
+        assert_eq!(layout.houses_of(40).count(), 5);
+        assert_eq!(layout.houses_of(1).count(), 3);
+        assert!(layout.peers(0).contains(&80));
+        assert!(layout.peers(8).contains(&72));
+        // adding the same rules again changes nothing
+        let layout = layout.with_variant(variant);
+        assert_eq!(layout.houses().len(), 29);
+        assert!("foo".parse::<Variant>().is_err());
+        assert_eq!("".parse::<Variant>(), Ok(Variant::default()));
+    }
+
+    #[test]
+    fn test_anti_knight_and_anti_king_variants() {
+        let variant: Variant = "anti-knight, anti-king".parse().unwrap();
+        assert!(variant.anti_knight && variant.anti_king && !variant.diagonal);
+        let layout = Layout::for_size(9).unwrap().with_variant(variant);
+        assert_eq!(layout.houses().len(), 27);
+        assert!(layout.has_peer_groups());
+        // a knight's move and a king's move from the top left corner
+        assert!(layout.peers(0).contains(&11));
+        assert!(layout.peers(0).contains(&19));
+        assert!(layout.peers(0).contains(&10));
+        assert!(!layout.peers(0).contains(&12));
+        assert!(!layout.peers(0).contains(&21));
+        // the peers stay when the cells are replaced by regions
+        let regions: Vec<usize> = (0..81).map(|pos_idx| pos_idx / 9).collect();
+        let layout = layout.with_regions(&regions).unwrap();
+        assert!(layout.peers(0).contains(&19));
+    }
+
+    #[test]
+    fn test_sandwich_lines() {
+        let layout = Layout::for_size(9).unwrap();
+        let layout = layout.with_sandwich(HouseKind::Column(8), 10).unwrap();
+        assert_eq!(layout.constraints()[0].positions()[1], 17);
+        let layout = layout.with_sandwich(HouseKind::Row(8), 0).unwrap();
+        assert_eq!(layout.constraints()[1].positions()[1], 73);
+        assert!(layout.clone().with_sandwich(HouseKind::Row(9), 0).is_err());
+        assert!(layout.with_sandwich(HouseKind::Cell(0), 0).is_err());
+    }
+
+    #[test]
+    fn test_hyper_variant() {
+        let variant: Variant = "hyper".parse().unwrap();
+        let layout = Layout::for_size(9).unwrap().with_variant(variant);
+        assert_eq!(layout.houses().len(), 31);
+        let window = &layout.houses()[27];
+        assert_eq!(window.kind, HouseKind::Window(0));
+        assert_eq!(window.positions, vec![10, 11, 12, 19, 20, 21, 28, 29, 30]);
+        assert_eq!(layout.houses()[30].positions[0], 5 * 9 + 5);
+        // the center of the second row is in no window
+        assert_eq!(layout.houses_of(13).count(), 3);
+        assert_eq!(layout.houses_of(10).count(), 4);
+
+        // a single window in the middle of 4x4, two windows for 6x6 with 2x3 cells
+        let layout = Layout::for_size(4).unwrap().with_variant(variant);
+        assert_eq!(layout.houses()[12].positions, vec![5, 6, 9, 10]);
+        let layout = Layout::for_size(6).unwrap().with_variant(variant);
+        assert_eq!(layout.houses().len(), 20);
+        assert_eq!(layout.houses()[19].positions, vec![25, 26, 27, 31, 32, 33]);
+    }
+
+    #[test]
+    fn test_inequalities() {
+        let layout = Layout::for_size(9).unwrap();
+        let layout = layout.with_inequality(1, 0).unwrap();
+        assert_eq!(layout.constraints()[0].positions(), &[0, 1]);
+        assert!(layout.clone().with_inequality(0, 10).is_err());
+        assert!(layout.with_inequality(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_extra_houses() {
+        let layout = Layout::for_size(4).unwrap();
+        let layout = layout.with_house(vec![0, 5, 10, 15]).unwrap();
+        assert_eq!(layout.houses()[12].kind, HouseKind::Extra(0));
+        assert!(layout.peers(0).contains(&15));
+        assert!(layout.clone().with_house(vec![0, 5, 10]).is_err());
+        assert!(layout.with_house(vec![0, 5, 10, 10]).is_err());
+    }
+
+    #[test]
+    fn test_regions() {
+        // cells of a 4x4 grid with the positions 2 and 4 swapped
+        let regions = [0, 0, 0, 1, 1, 0, 1, 1, 2, 2, 3, 3, 2, 2, 3, 3];
+        let layout = Layout::for_size(4).unwrap().with_regions(&regions).unwrap();
+        assert_eq!(layout.houses().len(), 12);
+        let region = &layout.houses()[8];
+        assert_eq!(region.kind, HouseKind::Region(0));
+        assert_eq!(region.positions, vec![0, 1, 2, 5]);
+        assert!(layout.peers(5).contains(&2));
+        assert!(!layout.peers(1).contains(&4));
+
+        // wrong number of positions and regions of the wrong size
+        assert!(Layout::for_size(4)
+            .unwrap()
+            .with_regions(&regions[1..])
+            .is_err());
+        let mut uneven = regions;
+        uneven[0] = 1;
+        assert!(Layout::for_size(4).unwrap().with_regions(&uneven).is_err());
+        uneven[0] = 4;
+        assert!(Layout::for_size(4).unwrap().with_regions(&uneven).is_err());
+    }
+
+    #[test]
+    fn test_samurai() {
+        let layout = Layout::samurai(3, 3);
+        assert_eq!(layout.board_size(), (21, 21));
+        assert_eq!(layout.num_positions(), 369);
+        // the shared corner cells are only added once
+        assert_eq!(layout.houses().len(), 5 * 27 - 4);
+        assert_eq!(layout.pos_idx(0, 9), None);
+        assert_eq!(layout.pos_idx(9, 0), None);
+        assert_eq!(layout.pos_idx(0, 21), None);
+        let center = layout.grids()[2].clone();
+        assert_eq!(layout.row_col(center[0]), (6, 6));
+        assert_eq!(layout.pos_idx(6, 6), Some(layout.grids()[0][60]));
+        // peers of the top left grid plus the rest of the row and column of the center grid
+        assert_eq!(layout.peers(center[0]).len(), 20 + 6 + 6);
+        assert_eq!(layout.peers(layout.grids()[0][0]).len(), 20);
+        assert_eq!(layout.houses_of(center[0]).count(), 5);
+        assert_eq!(Layout::for_num_fields(405).unwrap().num_positions(), 369);
+        // only five 9x9 grids make a samurai, a 9x9 grid one field short is rejected
+        assert!(Layout::for_num_fields(80).is_none());
+        assert!(Layout::for_num_fields(5 * 16).is_none());
+
+        // the rules of the variant apply to every grid
+        let layout = layout.with_variant("diagonal".parse().unwrap());
+        assert_eq!(layout.houses().len(), 5 * 27 - 4 + 10);
+        let diagonal = &layout.houses()[5 * 27 - 4 + 4];
+        assert_eq!(diagonal.to_string(), "diagonal of grid 3");
+        assert_eq!(diagonal.positions[0], center[0]);
+        assert!(layout.clone().with_sandwich(HouseKind::Row(0), 0).is_err());
+        assert!(layout.with_regions(&[0; 369]).is_err());
+    }
+
+    #[test]
+    fn test_symbols() {
+        let layout = Layout::for_size(16).unwrap();
+        assert_eq!(Layout::symbol(9), '9');
+        assert_eq!(Layout::symbol(16), 'G');
+        assert_eq!(layout.digit('g'), Some(16));
+        assert_eq!(layout.digit('H'), None);
+        assert_eq!(layout.digit('0'), None);
+        assert_eq!(layout.digit('.'), None);
+    }
+}
@@ -6,17 +6,68 @@ extern crate clap;
 extern crate env_logger;
 extern crate regex;
 
-use regex::Regex;
-use std::collections::HashSet;
+mod arrow;
+mod chess;
+mod constraint;
+mod dlx;
+mod fish;
+mod intersections;
+mod killer;
+mod kropki;
+mod layout;
+mod logic;
+mod restriction;
+mod sandwich;
+mod solver;
+mod subsets;
+mod thermo;
+mod wings;
+
+pub use constraint::{Constraint, Elimination};
+pub use dlx::{solve_sudoku_dlx, DlxSolver};
+pub use kropki::PairRelation;
+pub use layout::{House, HouseKind, Layout, Variant, MAX_SIZE};
+pub use logic::{Action, LogicalSolver, Step, Technique};
+pub use solver::{BacktrackingSolver, SolutionCount, SolveStats, Solver, SolverKind};
+
+use layout::grid_size;
+use restriction::named_digits;
 use std::fs;
-use std::hash::Hash;
+use std::sync::Arc;
 
 #[wasm_bindgen]
 pub fn wasm_solve_sudoku(input_str: &str) -> String {
+    wasm_solve_sudoku_with_solver(input_str, "backtracking")
+}
+
+/// Like `wasm_solve_sudoku`, but with the algorithm selected by name (see `SolverKind`).
+#[wasm_bindgen]
+pub fn wasm_solve_sudoku_with_solver(input_str: &str, solver_name: &str) -> String {
+    wasm_solve_sudoku_with_options(input_str, solver_name, "")
+}
+
+/// Like `wasm_solve_sudoku`, but with the algorithm and the variant (see `Variant`)
+/// selected by name, e.g. "dlx" and "diagonal". Returns an empty string for an unknown
+/// solver or variant name.
+#[wasm_bindgen]
+pub fn wasm_solve_sudoku_with_options(
+    input_str: &str,
+    solver_name: &str,
+    variant_names: &str,
+) -> String {
     let input_str = input_str.trim();
-    assert_eq!(input_str.len(), 81, "Incorrect length of input string!");
-    let sudoku_problem = SudokuCandidates::from_vec(convert_problem_str(input_str));
-    let solution_opt = solve_sudoku(sudoku_problem, 0);
+    let names = solver_name
+        .parse::<SolverKind>()
+        .and_then(|kind| Ok((kind, variant_names.parse::<Variant>()?)));
+    let (kind, variant) = match names {
+        Ok(names) => names,
+        Err(err) => {
+            println!("{}", err);
+            return String::new();
+        }
+    };
+    let sudoku_problem = problem_from_input_str(input_str, variant);
+    let solution_opt = sudoku_problem.and_then(|problem| kind.solver().solve(&problem));
     if let Some(solution) = solution_opt {
         solution.to_continuous_string()
     } else {
@@ -27,9 +78,21 @@ pub fn wasm_solve_sudoku(input_str: &str) -> String {
 
 #[wasm_bindgen]
 pub fn wasm_sudoku_contains_conflicts(input_str: &str) -> bool {
-    let input_str = input_str.trim();
-    assert_eq!(input_str.len(), 81, "Incorrect length of input string!");
-    let sudoku_problem = SudokuCandidates::from_vec(convert_problem_str(input_str));
+    wasm_sudoku_contains_conflicts_with_variant(input_str, "")
+}
+
+/// Like `wasm_sudoku_contains_conflicts`, taking the rules of the variant into account.
+/// An unknown variant name counts as a conflict.
+#[wasm_bindgen]
+pub fn wasm_sudoku_contains_conflicts_with_variant(input_str: &str, variant_names: &str) -> bool {
+    let variant = match variant_names.parse::<Variant>() {
+        Ok(variant) => variant,
+        Err(err) => {
+            println!("{}", err);
+            return true;
+        }
+    };
+    let sudoku_problem = problem_from_input_str(input_str.trim(), variant);
     if let Some(prob) = sudoku_problem {
         prob.has_unresolvable_conflicts()
     } else {
@@ -37,169 +100,387 @@ pub fn wasm_sudoku_contains_conflicts(input_str: &str) -> bool {
     }
 }
 
+/// Problem of a grid string from JS, None if its length fits no layout or the digits
+/// conflict.
+fn problem_from_input_str(input_str: &str, variant: Variant) -> Option<SudokuCandidates> {
+    let fields = convert_problem_str(input_str);
+    let layout = Layout::for_num_fields(fields.len())?;
+    let numbers = numbers_of_positions(&layout, &fields)?;
+    SudokuCandidates::from_vec_with_layout(Arc::new(layout.with_variant(variant)), numbers)
+}
+
+/// Set of candidate digits for a single position, stored as a bitmask.
+/// Bit `d` is set if digit `d` (1 up to the grid size) is still possible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidates(u32);
+
+impl Candidates {
+    pub const NONE: Candidates = Candidates(0);
+
+    /// All digits of a grid with the given size.
+    pub fn all(size: usize) -> Candidates {
+        Candidates(((1 << size) - 1) << 1)
+    }
+
+    pub fn single(digit: u8) -> Candidates {
+        Candidates(1 << digit)
+    }
+
+    pub fn contains(self, digit: u8) -> bool {
+        self.0 & (1 << digit) != 0
+    }
+
+    pub fn remove(&mut self, digit: u8) {
+        self.0 &= !(1 << digit);
+    }
+
+    /// Candidates of `self` that are not in `other`.
+    pub fn without(self, other: Candidates) -> Candidates {
+        Candidates(self.0 & !other.0)
+    }
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Smallest digit in the set.
+    pub fn first(self) -> Option<u8> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.0.trailing_zeros() as u8)
+        }
+    }
+
+    /// Largest digit in the set.
+    pub fn last(self) -> Option<u8> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(31 - self.0.leading_zeros() as u8)
+        }
+    }
+
+    /// All digits from `low` up to and including `high`, empty if `low > high`.
+    /// Digits above `MAX_SIZE` are ignored.
+    pub fn range(low: u8, high: u8) -> Candidates {
+        let high = high.min(MAX_SIZE as u8);
+        if low > high {
+            Candidates::NONE
+        } else {
+            Candidates(((1 << (high - low + 1)) - 1) << low)
+        }
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = u8> {
+        let mut bits = self.0;
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let digit = bits.trailing_zeros() as u8;
+            bits &= bits - 1;
+            Some(digit)
+        })
+    }
+}
+
+impl std::iter::FromIterator<u8> for Candidates {
+    fn from_iter<I: IntoIterator<Item = u8>>(digits: I) -> Candidates {
+        digits.into_iter().fold(Candidates::NONE, |cands, digit| {
+            cands | Candidates::single(digit)
+        })
+    }
+}
+
+impl std::ops::BitOr for Candidates {
+    type Output = Candidates;
+
+    fn bitor(self, rhs: Candidates) -> Candidates {
+        Candidates(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitAnd for Candidates {
+    type Output = Candidates;
+
+    fn bitand(self, rhs: Candidates) -> Candidates {
+        Candidates(self.0 & rhs.0)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SudokuCandidates {
-    grid: Vec<Vec<Vec<u8>>>,
+    layout: Arc<Layout>,
+    // candidates of each position, indexed like the positions of the layout
+    grid: Vec<Candidates>,
+    // candidates the problem was created from, before any propagation
+    initial: Arc<Vec<Candidates>>,
 }
 
+/// Splits a problem into the digits of its positions, 0 marks an empty position.
+/// Positions are either given as one symbol each (1-9, followed by A-Z for larger grids)
+/// or comma separated, which also allows multi-digit numbers like 12.
+/// Anything else, including symbols too large for the grid, represents an empty position.
 fn convert_problem_str(problem_raw_in: &str) -> Vec<u8> {
-    problem_raw_in
-        .split("")
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .map(|s| s.parse().unwrap())
+    let problem_raw_in = problem_raw_in.trim_end();
+    let tokens: Vec<&str> = if problem_raw_in.contains(',') {
+        let mut tokens: Vec<&str> = problem_raw_in.split(',').map(|s| s.trim()).collect();
+        if tokens.last() == Some(&"") {
+            tokens.pop();
+        }
+        tokens
+    } else {
+        problem_raw_in
+            .char_indices()
+            .map(|(i, c)| &problem_raw_in[i..i + c.len_utf8()])
+            .collect()
+    };
+
+    let size = match grid_size(tokens.len()) {
+        Some((size, _)) => size as u32,
+        None => (tokens.len() as f64).sqrt() as u32,
+    };
+    tokens
+        .iter()
+        .map(|token| {
+            let mut chars = token.chars();
+            let digit = match (token.parse::<u32>(), chars.next(), chars.next()) {
+                (Ok(number), _, _) => number,
+                (_, Some(symbol), None) => symbol.to_digit(36).unwrap_or(0),
+                _ => 0,
+            };
+            if digit <= size {
+                digit as u8
+            } else {
+                0
+            }
+        })
         .collect()
 }
 
 impl SudokuCandidates {
-    fn initial() -> SudokuCandidates {
-        SudokuCandidates {
-            // 9 x 9 grid of u8s
-            grid: vec![vec![(1..10).collect::<Vec<u8>>(); 9]; 9],
-        }
-    }
-
+    /// Problem with the standard layout for the number of positions, e.g. 3x3 cells for 81.
     fn from_vec(numbers: Vec<u8>) -> Option<SudokuCandidates> {
-        assert!(numbers.len() == 81);
-        let mut problem = SudokuCandidates::initial();
+        let layout = Layout::for_num_positions(numbers.len())
+            .unwrap_or_else(|| panic!("Unsupported number of positions: {}", numbers.len()));
+        SudokuCandidates::from_vec_with_layout(Arc::new(layout), numbers)
+    }
 
-        for (i, item) in numbers.iter().enumerate() {
-            if *item == 0_u8 {
-                continue;
-            }
-            let row_idx = i / 9;
-            let col_idx = i % 9;
+    /// Problem with the digit of each position of the layout, 0 marks an empty position.
+    /// Returns None if a digit is larger than the grid size or the digits contradict the
+    /// rules of the layout.
+    pub fn from_vec_with_layout(layout: Arc<Layout>, numbers: Vec<u8>) -> Option<SudokuCandidates> {
+        let size = layout.size();
+        if numbers.iter().any(|digit| *digit as usize > size) {
+            return None;
+        }
+        let all = Candidates::all(size);
+        let candidates = numbers
+            .iter()
+            .map(|number| match number {
+                0 => all,
+                digit => Candidates::single(*digit),
+            })
+            .collect();
+        SudokuCandidates::from_candidates(layout, candidates)
+    }
 
-            problem.grid[row_idx][col_idx] = vec![*item];
-            let prob_tmp_opt = remove_duplicates(&problem, row_idx, col_idx, *item);
+    /// Problem with the remaining candidates of each position of the layout, e.g. from a
+    /// pencil-marked grid. Positions with a single candidate are placed, digits larger than
+    /// the grid size are ignored.
+    /// Returns None if the number of positions does not match the layout or the candidates
+    /// contradict the rules of the layout.
+    pub fn from_candidates(
+        layout: Arc<Layout>,
+        candidates: Vec<Candidates>,
+    ) -> Option<SudokuCandidates> {
+        if candidates.len() != layout.num_positions() {
+            return None;
+        }
+        let all = Candidates::all(layout.size());
+        let candidates: Vec<Candidates> = candidates.into_iter().map(|cands| cands & all).collect();
+        let mut problem = SudokuCandidates {
+            grid: candidates.clone(),
+            initial: Arc::new(candidates),
+            layout,
+        };
+        // nothing is ever undone here, the trail is just required by the elimination
+        let mut trail = vec![];
 
-            if let Some(prob) = prob_tmp_opt {
-                problem = prob;
-            } else {
+        for pos_idx in 0..problem.grid.len() {
+            let cands = problem.get(pos_idx);
+            let consistent = match cands.len() {
+                0 => false,
+                1 => problem.eliminate_from_peers(pos_idx, cands.first().unwrap(), &mut trail),
+                _ => true,
+            };
+            if !consistent {
                 return None;
             }
         }
+        if !problem.apply_constraints(&mut trail) {
+            return None;
+        }
         Some(problem)
     }
 
-    fn to_continuous_string(&self) -> String {
-        let mut res_str: String = "".to_owned();
-        for row_idx in 0..9 {
-            for col_idx in 0..9 {
-                let el = self.grid[row_idx][col_idx][0].to_string();
-                res_str += &el;
-            }
+    /// Empty 9x9 grid with the given candidates at some positions, e.g. to set up a
+    /// technique in tests.
+    #[cfg(test)]
+    pub(crate) fn pencil_marks(marks: &[(usize, &[u8])]) -> SudokuCandidates {
+        let mut grid = vec![Candidates::all(9); 81];
+        for (pos_idx, digits) in marks {
+            grid[*pos_idx] = digits.iter().copied().collect();
         }
-        res_str
+        SudokuCandidates::from_candidates(Arc::new(Layout::new(3, 3)), grid).unwrap()
     }
 
-    fn get_duplicates_in_column(
-        &self,
-        element: u8,
-        el_row_idx: usize,
-        el_col_idx: usize,
-    ) -> HashSet<(usize, usize)> {
-        let mut duplicates: HashSet<(usize, usize)> = HashSet::new();
-        // find duplicates in column
-        for row_idx in 0..9 {
-            if row_idx == el_row_idx {
-                continue;
-            }
-            if self.grid[row_idx][el_col_idx].iter().any(|x| *x == element) {
-                duplicates.insert((row_idx, el_col_idx));
-            }
-        }
-        duplicates
+    pub fn layout(&self) -> &Layout {
+        &self.layout
     }
 
-    fn get_duplicates_in_row(
-        &self,
-        element: u8,
-        el_row_idx: usize,
-        el_col_idx: usize,
-    ) -> HashSet<(usize, usize)> {
-        let mut duplicates: HashSet<(usize, usize)> = HashSet::new();
-        // find duplicates in row
-        for col_idx in 0..9 {
-            if col_idx == el_col_idx {
-                continue;
-            }
-            if self.grid[el_row_idx][col_idx].iter().any(|x| *x == element) {
-                duplicates.insert((el_row_idx, col_idx));
-            }
-        }
-        duplicates
-    }
-
-    fn get_duplicates_in_cell(
-        &self,
-        element: u8,
-        el_row_idx: usize,
-        el_col_idx: usize,
-    ) -> HashSet<(usize, usize)> {
-        let mut duplicates: HashSet<(usize, usize)> = HashSet::new();
-        // find duplicates in cell
-        let cell_row_idx = el_row_idx / 3;
-        let cell_col_idx = el_col_idx / 3;
-
-        for row_idx_in_cell in 0..3 {
-            for col_idx_in_cell in 0..3 {
-                let row_idx = cell_row_idx * 3 + row_idx_in_cell;
-                let col_idx = cell_col_idx * 3 + col_idx_in_cell;
-                if row_idx == el_row_idx && col_idx == el_col_idx {
+    /// Remaining candidates of the position.
+    pub fn get(&self, pos_idx: usize) -> Candidates {
+        self.grid[pos_idx]
+    }
+
+    fn set(&mut self, pos_idx: usize, candidates: Candidates) {
+        self.grid[pos_idx] = candidates;
+    }
+
+    /// Digits in the text format, a shared position of a samurai sudoku appears in each of
+    /// its grids.
+    fn to_continuous_string(&self) -> String {
+        self.layout
+            .text_positions()
+            .map(|pos_idx| self.get(pos_idx).first().map_or('0', Layout::symbol))
+            .collect()
+    }
+
+    /// Removes `el` from all positions sharing a house with the given position.
+    /// Positions that end up with a single candidate are propagated in turn.
+    /// The previous candidates of every modified position are pushed to `trail`.
+    /// Returns false if a position runs out of candidates.
+    fn eliminate_from_peers(
+        &mut self,
+        el_pos_idx: usize,
+        el: u8,
+        trail: &mut Vec<TrailEntry>,
+    ) -> bool {
+        let layout = Arc::clone(&self.layout);
+        let mut pending = vec![(el_pos_idx, el)];
+        while let Some((el_pos_idx, el)) = pending.pop() {
+            debug!("removing {} {}", el_pos_idx, el);
+            for dup_pos_idx in layout.peers(el_pos_idx) {
+                let mut cands = self.get(*dup_pos_idx);
+                if !cands.contains(el) {
                     continue;
                 }
-                if self.grid[row_idx][col_idx].iter().any(|x| *x == element) {
-                    duplicates.insert((row_idx, col_idx));
+                trail.push(TrailEntry {
+                    pos_idx: *dup_pos_idx,
+                    candidates: cands,
+                });
+                cands.remove(el);
+                self.set(*dup_pos_idx, cands);
+                if cands.is_empty() {
+                    // conflict detected
+                    return false;
+                } else if cands.len() == 1 {
+                    pending.push((*dup_pos_idx, cands.first().unwrap()));
                 }
             }
         }
-        duplicates
+        true
     }
 
-    fn get_duplicates(
-        &self,
-        element: u8,
-        el_row_idx: usize,
-        el_col_idx: usize,
-    ) -> HashSet<(usize, usize)> {
-        let mut duplicates: HashSet<(usize, usize)> = HashSet::new();
-
-        let row_duplicates = self.get_duplicates_in_row(element, el_row_idx, el_col_idx);
-        duplicates.extend(row_duplicates);
-
-        let col_duplicates = self.get_duplicates_in_column(element, el_row_idx, el_col_idx);
-        duplicates.extend(&col_duplicates);
-
-        let cell_duplicates = self.get_duplicates_in_cell(element, el_row_idx, el_col_idx);
-        duplicates.extend(&cell_duplicates);
-
-        duplicates
+    /// Removes the candidates of the elimination from its position, a position left with a
+    /// single candidate is propagated to its peers.
+    /// Returns None if a position runs out of candidates, otherwise whether anything changed.
+    fn apply_elimination(
+        &mut self,
+        elimination: &Elimination,
+        trail: &mut Vec<TrailEntry>,
+    ) -> Option<bool> {
+        let cands = self.get(elimination.pos_idx);
+        let remaining = cands.without(elimination.candidates);
+        if remaining == cands {
+            return Some(false);
+        }
+        trail.push(TrailEntry {
+            pos_idx: elimination.pos_idx,
+            candidates: cands,
+        });
+        self.set(elimination.pos_idx, remaining);
+        let consistent = match remaining.len() {
+            0 => false,
+            1 => self.eliminate_from_peers(elimination.pos_idx, remaining.first().unwrap(), trail),
+            _ => true,
+        };
+        if consistent {
+            Some(true)
+        } else {
+            None
+        }
     }
 
-    fn is_correct(&self) -> bool {
-        for row in &self.grid {
-            for col in row {
-                if col.len() != 1 {
-                    return false;
+    /// Removes the candidates ruled out by the rules of the layout, the houses as well as the
+    /// additional constraints (e.g. killer cages), until nothing changes anymore.
+    /// The previous candidates of every modified position are pushed to `trail`.
+    /// Returns false if a position runs out of candidates.
+    fn apply_constraints(&mut self, trail: &mut Vec<TrailEntry>) -> bool {
+        let layout = Arc::clone(&self.layout);
+        loop {
+            let mut changed = false;
+            for constraint in layout.rules() {
+                for elimination in constraint.prune(self) {
+                    match self.apply_elimination(&elimination, trail) {
+                        Some(modified) => changed |= modified,
+                        None => return false,
+                    }
                 }
             }
-        }
-        for col_idx in 0..9 {
-            let mut sum_col_elems = 0;
-            for row_idx in 0..9 {
-                sum_col_elems += self.grid[row_idx][col_idx][0];
+            if !changed {
+                return true;
             }
-            if sum_col_elems != 9 * 10 / 2 {
+        }
+    }
+
+    /// Like `apply_constraints`, additionally removes the candidates ruled out by naked and
+    /// hidden subsets in the houses. Used by the search, which needs fewer guesses this way.
+    /// Returns false if a position runs out of candidates.
+    fn propagate(&mut self, trail: &mut Vec<TrailEntry>) -> bool {
+        loop {
+            if !self.apply_constraints(trail) {
                 return false;
             }
-        }
-        for row_idx in 0..9 {
-            let mut sum_row_elems = 0;
-            for col_idx in 0..9 {
-                sum_row_elems += self.grid[row_idx][col_idx][0];
+            match subsets::apply_subsets(self, trail) {
+                Some(true) => {}
+                Some(false) => return true,
+                None => return false,
             }
-            if sum_row_elems != 9 * 10 / 2 {
+        }
+    }
+
+    /// True if every position is filled, every house contains all digits and the additional
+    /// constraints (e.g. arrow sums) are satisfied.
+    fn is_correct(&self) -> bool {
+        if self.grid.iter().any(|cands| cands.len() != 1) {
+            return false;
+        }
+        let all = Candidates::all(self.layout.size());
+        for house in self.layout.houses() {
+            let house_elems = house
+                .positions
+                .iter()
+                .fold(Candidates::NONE, |elems, pos_idx| {
+                    elems | self.get(*pos_idx)
+                });
+            if house_elems != all {
                 return false;
             }
         }
@@ -207,108 +488,79 @@ impl SudokuCandidates {
     }
 
     fn has_unresolvable_conflicts(&self) -> bool {
-        // rows okay
-        for row_idx in 0..9 {
-            let mut elems = vec![];
-            for col_idx in 0..9 {
-                if self.grid[row_idx][col_idx].len() == 1 {
-                    elems.push(self.grid[row_idx][col_idx][0]);
-                }
-            }
-            if !has_unique_elements(elems) {
-                debug!("Unresolvable conflict at row: {}", row_idx);
+        for house in self.layout.houses() {
+            if house.has_conflict(self) {
+                debug!("Unresolvable conflict at {}", house);
                 return true;
             }
         }
-
-        // cols okay
-        for col_idx in 0..9 {
-            let mut elems = vec![];
-            for row_idx in 0..9 {
-                if self.grid[row_idx][col_idx].len() == 1 {
-                    elems.push(self.grid[row_idx][col_idx][0]);
+        if self.layout.has_peer_groups() {
+            for pos_idx in 0..self.grid.len() {
+                let cands = self.get(pos_idx);
+                if cands.len() == 1
+                    && self
+                        .layout
+                        .peers(pos_idx)
+                        .iter()
+                        .any(|peer_idx| self.get(*peer_idx) == cands)
+                {
+                    let (row_idx, col_idx) = self.layout.row_col(pos_idx);
+                    debug!("Unresolvable conflict at r{}c{}", row_idx + 1, col_idx + 1);
+                    return true;
                 }
             }
-            if !has_unique_elements(elems) {
-                debug!("Unresolvable conflict at col: {}", col_idx);
-                return true;
-            }
         }
-
-        // cells okay
-        for meta_col_idx in 0..3 {
-            for meta_row_idx in 0..3 {
-                let mut cell_elems = vec![];
-                for col_idx_in_cell in 0..3 {
-                    for row_idx_in_cell in 0..3 {
-                        let row_idx = meta_row_idx * 3 + row_idx_in_cell;
-                        let col_idx = meta_col_idx * 3 + col_idx_in_cell;
-                        if self.grid[row_idx][col_idx].len() == 1 {
-                            cell_elems.push(self.grid[row_idx][col_idx][0]);
-                        }
-                    }
-                }
-                if !has_unique_elements(cell_elems) {
-                    debug!(
-                        "Unresolvable conflict at cell: meta_row_idx {} meta_col_idx {}",
-                        meta_row_idx, meta_col_idx
-                    );
-                    return true;
-                }
+        for constraint in self.layout.constraints() {
+            if constraint.has_conflict(self) {
+                debug!("Unresolvable conflict at {:?}", constraint);
+                return true;
             }
         }
-
         false
     }
 
     fn get_best_place_and_number_to_insert(&self) -> Option<InsertionCandidate> {
         // get place with least options, but more than one option
 
-        let mut best_row = 0;
-        let mut best_col = 0;
-        let mut best_els = vec![];
-        let mut shortest_len = 100;
+        let mut best_pos = 0;
+        let mut best_els = Candidates::NONE;
+        let mut shortest_len = usize::MAX;
 
-        // check for single option in row/col/cell
+        // check for single option in one of the houses
         // elemnt mindestens länge 2
-        'single_el_search: for row_idx in 0..9 {
-            for col_idx in 0..9 {
-                let current_prob_len = self.grid[row_idx][col_idx].len();
-                if current_prob_len == 1 {
-                    continue;
-                }
-                for el in &self.grid[row_idx][col_idx] {
-                    // check if single possible el
-                    if is_single_element_in_col(self, row_idx, col_idx, *el)
-                        || is_single_element_in_row(self, row_idx, col_idx, *el)
-                        || is_single_element_in_cell(self, row_idx, col_idx, *el)
-                    {
-                        best_row = row_idx;
-                        best_col = col_idx;
-                        best_els = vec![*el];
-                        break 'single_el_search;
-                    }
+        'single_el_search: for pos_idx in 0..self.grid.len() {
+            let cands = self.get(pos_idx);
+            if cands.len() == 1 {
+                continue;
+            }
+            for el in cands.iter() {
+                // check if single possible el
+                if self
+                    .layout
+                    .houses_of(pos_idx)
+                    .any(|house| is_single_element_in_house(self, house, pos_idx, el))
+                {
+                    best_pos = pos_idx;
+                    best_els = Candidates::single(el);
+                    break 'single_el_search;
                 }
             }
         }
 
         if best_els.is_empty() {
-            'outer: for row_idx in 0..9 {
-                for col_idx in 0..9 {
-                    let current_prob_len = self.grid[row_idx][col_idx].len();
-                    if current_prob_len == 1 {
-                        continue;
-                    } else if current_prob_len == 2 {
-                        best_row = row_idx;
-                        best_col = col_idx;
-                        best_els = self.grid[row_idx][col_idx].clone();
-                        break 'outer;
-                    } else if current_prob_len > 2 && current_prob_len < shortest_len {
-                        best_row = row_idx;
-                        best_col = col_idx;
-                        best_els = self.grid[row_idx][col_idx].clone();
-                        shortest_len = current_prob_len;
-                    }
+            for pos_idx in 0..self.grid.len() {
+                let cands = self.get(pos_idx);
+                let current_prob_len = cands.len();
+                if current_prob_len == 1 {
+                    continue;
+                } else if current_prob_len == 2 {
+                    best_pos = pos_idx;
+                    best_els = cands;
+                    break;
+                } else if current_prob_len > 2 && current_prob_len < shortest_len {
+                    best_pos = pos_idx;
+                    best_els = cands;
+                    shortest_len = current_prob_len;
                 }
             }
         }
@@ -317,8 +569,7 @@ impl SudokuCandidates {
         }
 
         Some(InsertionCandidate {
-            row_idx: best_row,
-            col_idx: best_col,
+            pos_idx: best_pos,
             candidates: best_els,
         })
     }
@@ -326,14 +577,20 @@ impl SudokuCandidates {
 
 impl std::fmt::Display for SudokuCandidates {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let size = self.layout.size();
+        let (board_rows, board_cols) = self.layout.board_size();
         let mut some_str = "".to_string();
-        for row_idx in 0..9 {
-            for col_idx in 0..9 {
-                let cand_str = self.grid[row_idx][col_idx]
-                    .iter()
-                    .map(|i| i.to_string())
-                    .collect::<String>();
-                let sym = format!("{: >9},", cand_str);
+        for row_idx in 0..board_rows {
+            for col_idx in 0..board_cols {
+                let sym = match self.layout.pos_idx(row_idx, col_idx) {
+                    Some(pos_idx) => {
+                        let cands = self.get(pos_idx);
+                        let cand_str = cands.iter().map(Layout::symbol).collect::<String>();
+                        format!("{: >width$},", cand_str, width = size)
+                    }
+                    // outside of the grids of a samurai sudoku
+                    None => " ".repeat(size + 1),
+                };
                 some_str.push_str(&sym);
             }
             some_str.push('\n');
@@ -343,221 +600,583 @@ impl std::fmt::Display for SudokuCandidates {
     }
 }
 
-pub fn parse_sudokus(filepath: &str) -> Vec<SudokuCandidates> {
-    let contents = fs::read_to_string(filepath).expect("Something went wrong reading the file");
-    let lines = contents.lines();
-
-    let mut candidates = vec![];
-    for line in lines {
-        // replace any non numeric characters in the line with 0
-        let non_numeric_chars = Regex::new(r"[^0-9]").unwrap();
-        let line_no_commas = line.replace(",", "");
-        let problem_str_raw = non_numeric_chars
-            .replace_all(&line_no_commas, "0")
-            .into_owned();
-
-        let problem_raw: Vec<u8> = convert_problem_str(&problem_str_raw);
-        let cand = SudokuCandidates::from_vec(problem_raw);
-
-        if let Some(cand) = cand {
-            candidates.push(cand);
-        } else {
+/// Parses a single problem, the grid size is derived from the number of positions.
+/// Empty fields can be represented by any character that is not a valid digit symbol.
+/// The grid may be followed by lines with additional constraints, see `parse_sudokus`.
+/// Returns None if the problem has an unsupported size, invalid constraints or contains conflicts.
+pub fn parse_sudoku(problem_str: &str) -> Option<SudokuCandidates> {
+    parse_sudoku_with_variant(problem_str, Variant::default())
+}
+
+/// Like `parse_sudoku`, with the additional rules of the variant.
+pub fn parse_sudoku_with_variant(problem_str: &str, variant: Variant) -> Option<SudokuCandidates> {
+    let lines: Vec<&str> = problem_str
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    parse_sudoku_cached(&lines, variant, &mut None)
+}
+
+/// Like `parse_sudoku_with_variant`, but tells input that cannot be read apart from givens
+/// that contradict the rules: returns an error with the reason for the former and None for
+/// the latter.
+pub fn try_parse_sudoku_with_variant(
+    problem_str: &str,
+    variant: Variant,
+) -> Result<Option<SudokuCandidates>, String> {
+    let lines: Vec<&str> = problem_str
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if lines.is_empty() {
+        return Err("empty problem".to_owned());
+    }
+    try_parse_sudoku_cached(&lines, variant, &mut None)
+}
+
+/// Lines of the form "name: arguments" add constraints to the problem above them.
+fn split_directive(line: &str) -> Option<(&str, &str)> {
+    let (name, args) = line.split_once(':')?;
+    let name = name.trim();
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+        Some((name, args.trim()))
+    } else {
+        None
+    }
+}
+
+/// Parses a position given as "r<row>c<column>" of the board, starting at 1.
+fn parse_position(token: &str, layout: &Layout) -> Result<usize, String> {
+    let lower = token.to_ascii_lowercase();
+    let parsed = lower
+        .strip_prefix('r')
+        .and_then(|rest| rest.split_once('c'))
+        .and_then(|(row, col)| Some((row.parse::<usize>().ok()?, col.parse::<usize>().ok()?)));
+    parsed
+        .filter(|(row, col)| *row >= 1 && *col >= 1)
+        .and_then(|(row, col)| layout.pos_idx(row - 1, col - 1))
+        .ok_or_else(|| format!("Invalid position {}", token))
+}
+
+fn parse_positions<'a>(
+    tokens: impl Iterator<Item = &'a str>,
+    layout: &Layout,
+) -> Result<Vec<usize>, String> {
+    tokens.map(|token| parse_position(token, layout)).collect()
+}
+
+/// Adds the constraint described by a directive line to the layout.
+fn apply_directive(layout: Layout, name: &str, args: &str) -> Result<Layout, String> {
+    let mut tokens = args.split_whitespace();
+    match name {
+        "cage" => {
+            let sum = tokens
+                .next()
+                .and_then(|sum| sum.parse::<u32>().ok())
+                .ok_or_else(|| format!("Missing sum of cage {}", args))?;
+            let positions = parse_positions(tokens, &layout)?;
+            layout.with_cage(positions, sum)
+        }
+        "thermo" => {
+            let positions = parse_positions(tokens, &layout)?;
+            layout.with_thermometer(positions)
+        }
+        "white" | "black" | "x" | "v" => {
+            // comma separated pairs of positions
+            let relation: PairRelation = name.parse()?;
+            args.split(',').try_fold(layout, |layout, pair| {
+                let positions = parse_positions(pair.split_whitespace(), &layout)?;
+                if positions.len() != 2 {
+                    return Err(format!("Expected two positions for marker {}", pair.trim()));
+                }
+                layout.with_marker(relation, positions[0], positions[1])
+            })
+        }
+        "negative" => args
+            .split(',')
+            .map(|name| name.trim().parse::<PairRelation>())
+            .try_fold(layout, |layout, relation| {
+                Ok(layout.with_negative_constraint(relation?))
+            }),
+        "sandwich-rows" | "sandwich-columns" => {
+            // one clue per row or column, "." or "-" if there is none
+            let clues: Vec<&str> = tokens.collect();
+            if clues.len() != layout.size() {
+                return Err(format!(
+                    "Expected {} sandwich clues, got {}",
+                    layout.size(),
+                    clues.len()
+                ));
+            }
+            clues
+                .into_iter()
+                .enumerate()
+                .filter(|(_, clue)| *clue != "." && *clue != "-")
+                .try_fold(layout, |layout, (idx, clue)| {
+                    let sum = clue
+                        .parse::<u32>()
+                        .map_err(|_| format!("Invalid sandwich clue {}", clue))?;
+                    let line = if name == "sandwich-rows" {
+                        HouseKind::Row(idx)
+                    } else {
+                        HouseKind::Column(idx)
+                    };
+                    layout.with_sandwich(line, sum)
+                })
+        }
+        "greater-rows" | "greater-columns" => {
+            // one token per row or column of the board with the signs between neighboring
+            // positions from left to right or top to bottom, "." if there is none
+            let rows = name == "greater-rows";
+            let (board_rows, board_cols) = layout.board_size();
+            let (num_lines, line_len) = if rows {
+                (board_rows, board_cols)
+            } else {
+                (board_cols, board_rows)
+            };
+            let lines: Vec<&str> = tokens.collect();
+            if lines.len() != num_lines {
+                return Err(format!(
+                    "Expected {} lines of inequality signs, got {}",
+                    num_lines,
+                    lines.len()
+                ));
+            }
+            let mut layout = layout;
+            for (line_idx, signs) in lines.into_iter().enumerate() {
+                if signs.chars().count() != line_len - 1 {
+                    return Err(format!(
+                        "Expected {} inequality signs, got {}",
+                        line_len - 1,
+                        signs
+                    ));
+                }
+                for (idx, sign) in signs.chars().enumerate() {
+                    let ((first_row, first_col), (second_row, second_col)) = if rows {
+                        ((line_idx, idx), (line_idx, idx + 1))
+                    } else {
+                        ((idx, line_idx), (idx + 1, line_idx))
+                    };
+                    let (first, second) = match (
+                        layout.pos_idx(first_row, first_col),
+                        layout.pos_idx(second_row, second_col),
+                    ) {
+                        (Some(first), Some(second)) => (first, second),
+                        _ if sign == '.' => continue,
+                        _ => return Err(format!("Inequality sign {} outside of the grids", sign)),
+                    };
+                    layout = match sign {
+                        '<' => layout.with_inequality(second, first)?,
+                        '>' => layout.with_inequality(first, second)?,
+                        '.' => layout,
+                        _ => return Err(format!("Invalid inequality sign {}", sign)),
+                    };
+                }
+            }
+            Ok(layout)
+        }
+        "arrow" => {
+            // circle positions, ">" and the shaft positions
+            let (circle, shaft) = args
+                .split_once('>')
+                .ok_or_else(|| format!("Missing > between circle and shaft of arrow {}", args))?;
+            let circle = parse_positions(circle.split_whitespace(), &layout)?;
+            let shaft = parse_positions(shaft.split_whitespace(), &layout)?;
+            layout.with_arrow(circle, shaft)
+        }
+        "even" | "odd" | "low" | "high" => {
+            let allowed = named_digits(name, layout.size())
+                .ok_or_else(|| format!("Unknown digit set {}", name))?;
+            let positions = parse_positions(tokens, &layout)?;
+            layout.with_restriction(positions, allowed)
+        }
+        "candidates" => {
+            // the allowed digits as symbols, followed by the positions
+            let symbols = tokens
+                .next()
+                .ok_or_else(|| format!("Missing digits of candidates {}", args))?;
+            let allowed = symbols
+                .chars()
+                .map(|symbol| {
+                    layout
+                        .digit(symbol)
+                        .ok_or_else(|| format!("Invalid candidate {}", symbol))
+                })
+                .collect::<Result<Candidates, String>>()?;
+            let positions = parse_positions(tokens, &layout)?;
+            layout.with_restriction(positions, allowed)
+        }
+        "house" => {
+            let positions = parse_positions(tokens, &layout)?;
+            layout.with_house(positions)
+        }
+        "regions" => {
+            // one label per position, regions are numbered in order of their first appearance
+            let mut labels: Vec<char> = vec![];
+            let region_of_pos: Vec<usize> = args
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .map(
+                    |label| match labels.iter().position(|known| *known == label) {
+                        Some(region_idx) => region_idx,
+                        None => {
+                            labels.push(label);
+                            labels.len() - 1
+                        }
+                    },
+                )
+                .collect();
+            layout.with_regions(&region_of_pos)
+        }
+        "variant" => Ok(layout.with_variant(args.parse()?)),
+        _ => Err(format!("Unknown constraint {}", name)),
+    }
+}
+
+/// Like `try_parse_sudoku_cached`, printing why a problem could not be parsed.
+fn parse_sudoku_cached(
+    lines: &[&str],
+    variant: Variant,
+    layout_cache: &mut Option<Arc<Layout>>,
+) -> Option<SudokuCandidates> {
+    match try_parse_sudoku_cached(lines, variant, layout_cache) {
+        Ok(Some(problem)) => Some(problem),
+        Ok(None) => {
             println!(
                 "Failed to parse sudoku from {} - possibly containts conflicts.",
-                &problem_str_raw
+                lines[0]
             );
+            None
+        }
+        Err(err) => {
+            println!("Failed to parse sudoku from {} - {}.", lines[0], err);
+            None
         }
     }
-    candidates
 }
 
-fn remove_duplicates(
-    problem: &SudokuCandidates,
-    el_row_idx: usize,
-    el_col_idx: usize,
-    el: u8,
-) -> Option<SudokuCandidates> {
-    debug!("removing {} {} {}", el_row_idx, el_col_idx, el);
-    let mut problem = problem.clone();
-    let duplicates = problem.get_duplicates(el, el_row_idx, el_col_idx);
-    for (dup_row_idx, dup_col_idx) in duplicates {
-        let dupl_idx = problem.grid[dup_row_idx][dup_col_idx]
-            .iter()
-            .position(|x| *x == el);
-        if let Some(x) = dupl_idx {
-            problem.grid[dup_row_idx][dup_col_idx].remove(x);
-            if problem.grid[dup_row_idx][dup_col_idx].is_empty() {
-                // conflict detected
-                return None;
-            } else if problem.grid[dup_row_idx][dup_col_idx].len() == 1 {
-                let new_prob_opt = remove_duplicates(
-                    &problem,
-                    dup_row_idx,
-                    dup_col_idx,
-                    problem.grid[dup_row_idx][dup_col_idx][0],
-                );
-                if let Some(new_prob) = new_prob_opt {
-                    problem = new_prob;
-                } else {
-                    return None;
-                }
+/// Parses the grid in the first line and the directives in the remaining ones.
+/// Layouts without directives are cached and reused for the following problem if the size matches.
+fn try_parse_sudoku_cached(
+    lines: &[&str],
+    variant: Variant,
+    layout_cache: &mut Option<Arc<Layout>>,
+) -> Result<Option<SudokuCandidates>, String> {
+    let line = lines[0];
+    let problem_raw: Vec<u8> = convert_problem_str(line);
+    let directives = &lines[1..];
+    let cached_layout = layout_cache.as_ref().filter(|layout| {
+        layout.text_positions().count() == problem_raw.len() && directives.is_empty()
+    });
+    let layout = match cached_layout {
+        Some(layout) => Arc::clone(layout),
+        None => {
+            let mut layout = Layout::for_num_fields(problem_raw.len())
+                .ok_or_else(|| format!("unsupported number of positions {}", problem_raw.len()))?
+                .with_variant(variant);
+            for directive in directives {
+                layout = match split_directive(directive) {
+                    Some((name, args)) => apply_directive(layout, name, args)?,
+                    None => return Err(format!("Invalid constraint {}", directive)),
+                };
+            }
+            let layout = Arc::new(layout);
+            if directives.is_empty() {
+                *layout_cache = Some(Arc::clone(&layout));
             }
+            layout
         }
-    }
-    Some(problem)
-}
+    };
 
-fn has_unique_elements<T>(iter: T) -> bool
-where
-    T: IntoIterator,
-    T::Item: Eq + Hash,
-{
-    let mut uniq = HashSet::new();
-    iter.into_iter().all(move |x| uniq.insert(x))
+    Ok(numbers_of_positions(&layout, &problem_raw)
+        .and_then(|numbers| SudokuCandidates::from_vec_with_layout(layout, numbers)))
 }
 
-#[derive(Clone, Debug)]
-struct InsertionCandidate {
-    row_idx: usize,
-    col_idx: usize,
-    candidates: Vec<u8>,
+/// Digits of the positions for the digits of the fields in the text format, see
+/// `Layout::text_positions`. Returns None if two grids disagree on a shared position.
+fn numbers_of_positions(layout: &Layout, fields: &[u8]) -> Option<Vec<u8>> {
+    let mut numbers = vec![0; layout.num_positions()];
+    for (pos_idx, digit) in layout.text_positions().zip(fields) {
+        if *digit == 0 {
+            continue;
+        }
+        if numbers[pos_idx] != 0 && numbers[pos_idx] != *digit {
+            return None;
+        }
+        numbers[pos_idx] = *digit;
+    }
+    Some(numbers)
 }
 
-fn is_single_element_in_col(
-    problem: &SudokuCandidates,
-    row_idx: usize,
-    col_idx: usize,
-    el: u8,
-) -> bool {
-    for row_inner_idx in 0..9 {
-        if row_idx == row_inner_idx {
+/// Groups the lines of a file into problems: a grid line followed by its directive lines.
+/// Empty lines and lines starting with "#" are ignored.
+/// Returns the line number of each grid line (starting at 1) and the text of the problem,
+/// which can be passed to `parse_sudoku`.
+pub fn split_problems(contents: &str) -> Vec<(usize, String)> {
+    let mut problems: Vec<(usize, String)> = vec![];
+    for (line_idx, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
             continue;
         }
-        if problem.grid[row_inner_idx][col_idx]
-            .iter()
-            .any(|x| x == &el)
-        {
-            return false;
+        match problems.last_mut() {
+            Some((_, problem)) if split_directive(line).is_some() => {
+                problem.push('\n');
+                problem.push_str(line);
+            }
+            _ => problems.push((line_idx + 1, line.to_owned())),
         }
     }
-    true
+    problems
 }
 
-fn is_single_element_in_row(
-    problem: &SudokuCandidates,
-    row_idx: usize,
-    col_idx: usize,
-    el: u8,
-) -> bool {
-    for col_inner_idx in 0..9 {
-        if col_idx == col_inner_idx {
+/// Parses all problems of a file, one problem per line.
+/// A problem line can be followed by lines adding constraints to it, e.g.
+/// "cage: 15 r1c1 r1c2 r2c1" for a killer cage with sum 15,
+/// "thermo: r1c1 r1c2 r2c2" for a thermometer with the bulb at r1c1,
+/// "arrow: r1c1 > r1c2 r1c3" for an arrow with the circle at r1c1,
+/// "white: r1c1 r1c2, r5c5 r6c5" for kropki dots (also "black", "x" and "v"),
+/// "negative: white, black" if there is a dot between all adjacent positions it applies to,
+/// "sandwich-rows: 10 . 0 35 ..." for sandwich clues of all rows (also "sandwich-columns"),
+/// "greater-rows: ><.>>.>< ..." for the inequality signs of all rows (also "greater-columns"),
+/// "even: r1c1 r5c5" for positions restricted to even digits (also "odd", "low" and "high"),
+/// "candidates: 357 r1c1 r1c2" for positions restricted to the given digits,
+/// "house: r1c1 r2c2 ..." for an additional house containing every digit once or
+/// "regions: AAABBBCCC AAABBBCCC ..." for the irregular regions of a jigsaw sudoku.
+pub fn parse_sudokus(filepath: &str) -> Vec<SudokuCandidates> {
+    parse_sudokus_with_variant(filepath, Variant::default())
+}
+
+/// Like `parse_sudokus`, with the additional rules of the variant applied to all problems.
+pub fn parse_sudokus_with_variant(filepath: &str, variant: Variant) -> Vec<SudokuCandidates> {
+    let contents = fs::read_to_string(filepath).expect("Something went wrong reading the file");
+    let mut layout_cache = None;
+    split_problems(&contents)
+        .iter()
+        .filter_map(|(_, problem_str)| {
+            let lines: Vec<&str> = problem_str.lines().collect();
+            parse_sudoku_cached(&lines, variant, &mut layout_cache)
+        })
+        .collect()
+}
+
+/// Checks whether two positions with a single remaining candidate hold the same digit.
+fn has_duplicate_singles<T>(iter: T) -> bool
+where
+    T: IntoIterator<Item = Candidates>,
+{
+    let mut seen = Candidates::NONE;
+    for cands in iter {
+        if cands.len() != 1 {
             continue;
         }
-        if problem.grid[row_idx][col_inner_idx]
-            .iter()
-            .any(|x| x == &el)
-        {
-            return false;
+        if !(seen & cands).is_empty() {
+            return true;
         }
+        seen = seen | cands;
     }
-    true
+    false
+}
+
+#[derive(Clone, Debug)]
+struct InsertionCandidate {
+    pos_idx: usize,
+    candidates: Candidates,
 }
 
-fn is_single_element_in_cell(
+fn is_single_element_in_house(
     problem: &SudokuCandidates,
-    row_idx: usize,
-    col_idx: usize,
+    house: &House,
+    pos_idx: usize,
     el: u8,
 ) -> bool {
-    let cell_row_idx = row_idx / 3;
-    let cell_col_idx = col_idx / 3;
-
-    for row_idx_within_cell in 0..3 {
-        for col_idx_within_cell in 0..3 {
-            let row_idx_abs = 3 * cell_row_idx + row_idx_within_cell;
-            let col_idx_abs = 3 * cell_col_idx + col_idx_within_cell;
-            if row_idx_abs == row_idx && col_idx_abs == col_idx {
-                continue;
-            }
-            if problem.grid[row_idx_abs][col_idx_abs]
-                .iter()
-                .any(|x| x == &el)
-            {
-                return false;
-            }
-        }
-    }
-    true
+    house
+        .positions
+        .iter()
+        .filter(|inner_pos_idx| **inner_pos_idx != pos_idx)
+        .all(|inner_pos_idx| !problem.get(*inner_pos_idx).contains(el))
 }
 
-pub fn solve_sudoku(
-    problem_opt: Option<SudokuCandidates>,
-    recursion_depth: usize,
-) -> Option<SudokuCandidates> {
-    let recursion_depth = recursion_depth + 1;
+/// Candidates of a position before they were modified, used to undo eliminations on backtracking.
+#[derive(Clone, Copy, Debug)]
+struct TrailEntry {
+    pos_idx: usize,
+    candidates: Candidates,
+}
 
-    if let Some(problem) = problem_opt {
-        debug!("Depth: {}\n {}", recursion_depth, problem);
+/// A decision point of the search: the position that was guessed and the options not tried yet.
+#[derive(Clone, Debug)]
+struct Branch {
+    trail_len: usize,
+    pos_idx: usize,
+    remaining: Candidates,
+}
 
-        if problem.is_correct() {
-            // base case: only one possible number in each cell, solution found
-            Some(problem)
-        } else if problem.has_unresolvable_conflicts() {
-            None
-        } else {
-            let insertion_cand_opt = problem.get_best_place_and_number_to_insert();
+/// Depth first search working on a single mutable grid.
+/// Instead of cloning the grid for every guess, all eliminations are recorded on a trail
+/// and rolled back when a branch fails. The branches are kept on an explicit stack,
+/// so memory use is bounded by the number of positions and there is no recursion.
+#[derive(Clone, Debug)]
+struct BacktrackingSearch {
+    problem: SudokuCandidates,
+    trail: Vec<TrailEntry>,
+    stack: Vec<Branch>,
+    // set once a solution was returned, the next call has to continue with the next branch
+    resume: bool,
+    stats: SolveStats,
+}
 
-            debug!(
-                "Depth: {} Found insertion candidate {:?}",
-                recursion_depth, insertion_cand_opt
-            );
+impl BacktrackingSearch {
+    fn new(problem: SudokuCandidates) -> BacktrackingSearch {
+        BacktrackingSearch {
+            problem,
+            trail: vec![],
+            stack: vec![],
+            resume: false,
+            stats: SolveStats::default(),
+        }
+    }
 
-            if let Some(insertion_candidate) = insertion_cand_opt {
-                // try all possible solutions
-                let mut solution_candidates = vec![];
-
-                for el in insertion_candidate.candidates {
-                    let mut problem_bkp = problem.clone();
-
-                    problem_bkp.grid[insertion_candidate.row_idx][insertion_candidate.col_idx] =
-                        vec![el];
-                    let prob_tmp = remove_duplicates(
-                        &problem_bkp,
-                        insertion_candidate.row_idx,
-                        insertion_candidate.col_idx,
-                        el,
-                    );
-                    if prob_tmp.is_some() {
-                        solution_candidates.push(prob_tmp);
-                    }
-                }
+    /// Continues the search until the next solution is found.
+    /// Returns None once the search space is exhausted.
+    fn next_solution(&mut self) -> Option<SudokuCandidates> {
+        if std::mem::replace(&mut self.resume, true) {
+            if !self.try_next_option() {
+                return None;
+            }
+        } else if !self.problem.propagate(&mut self.trail) {
+            // the subsets of the problem itself already contradict each other
+            return None;
+        }
+
+        loop {
+            debug!("Depth: {}\n {}", self.stack.len(), self.problem);
+
+            if self.problem.is_correct() {
+                // only one possible number in each cell, solution found
+                return Some(self.problem.clone());
+            }
+
+            if !self.problem.has_unresolvable_conflicts() {
+                let insertion_cand_opt = self.problem.get_best_place_and_number_to_insert();
 
                 debug!(
-                    "Found {} candidates at depth {}",
-                    solution_candidates.len(),
-                    recursion_depth
+                    "Depth: {} Found insertion candidate {:?}",
+                    self.stack.len(),
+                    insertion_cand_opt
                 );
 
-                let solution = solution_candidates
-                    .iter()
-                    .cloned()
-                    .find_map(|x| solve_sudoku(x, recursion_depth));
-
-                match solution {
-                    Some(x) => solve_sudoku(Some(x), recursion_depth),
-                    _ => {
-                        debug!("No solution found at depth {}", recursion_depth);
-                        None
-                    }
+                if let Some(insertion_candidate) = insertion_cand_opt {
+                    self.stack.push(Branch {
+                        trail_len: self.trail.len(),
+                        pos_idx: insertion_candidate.pos_idx,
+                        remaining: insertion_candidate.candidates,
+                    });
+                } else {
+                    self.stats.dead_ends += 1;
                 }
             } else {
-                debug!("No candidates found at depth {}", recursion_depth);
-                None
+                self.stats.dead_ends += 1;
+            }
+
+            if !self.try_next_option() {
+                debug!("Search space exhausted");
+                return None;
             }
         }
-    } else {
-        debug!("Received None: {}", recursion_depth);
-        None
+    }
+
+    /// Undoes the deepest branch and inserts its next untried option.
+    /// Exhausted branches are dropped, returns false if no branch is left.
+    fn try_next_option(&mut self) -> bool {
+        while let Some(branch) = self.stack.last_mut() {
+            let el = match branch.remaining.first() {
+                Some(el) => el,
+                None => {
+                    debug!("No solution found at depth {}", self.stack.len());
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            branch.remaining.remove(el);
+            let (trail_len, pos_idx) = (branch.trail_len, branch.pos_idx);
+
+            self.undo(trail_len);
+            self.trail.push(TrailEntry {
+                pos_idx,
+                candidates: self.problem.get(pos_idx),
+            });
+            self.problem.set(pos_idx, Candidates::single(el));
+            self.stats.nodes += 1;
+            if self
+                .problem
+                .eliminate_from_peers(pos_idx, el, &mut self.trail)
+                && self.problem.propagate(&mut self.trail)
+            {
+                return true;
+            }
+            self.stats.dead_ends += 1;
+        }
+        false
+    }
+
+    fn undo(&mut self, trail_len: usize) {
+        while self.trail.len() > trail_len {
+            let entry = self.trail.pop().unwrap();
+            self.problem.grid[entry.pos_idx] = entry.candidates;
+        }
+    }
+}
+
+/// Solves the problem, returns None if it has no solution.
+/// The search is iterative, `recursion_depth` is only kept for compatibility and is ignored.
+pub fn solve_sudoku(
+    problem_opt: Option<SudokuCandidates>,
+    _recursion_depth: usize,
+) -> Option<SudokuCandidates> {
+    match problem_opt {
+        Some(problem) => BacktrackingSearch::new(problem).next_solution(),
+        None => {
+            debug!("Received None");
+            None
+        }
+    }
+}
+
+/// Lazy iterator over all solutions of a problem, see `all_solutions`.
+#[derive(Clone, Debug)]
+pub struct Solutions {
+    search: Option<BacktrackingSearch>,
+}
+
+impl Iterator for Solutions {
+    type Item = SudokuCandidates;
+
+    fn next(&mut self) -> Option<SudokuCandidates> {
+        self.search.as_mut()?.next_solution()
+    }
+}
+
+impl std::iter::FusedIterator for Solutions {}
+
+/// Enumerates all solutions of the problem in a deterministic order.
+/// Solutions are only searched for when requested, so taking the first few solutions
+/// of a problem with a huge number of solutions is cheap.
+pub fn all_solutions(problem_opt: Option<SudokuCandidates>) -> Solutions {
+    Solutions {
+        search: problem_opt.map(BacktrackingSearch::new),
+    }
+}
+
+/// Counts the solutions of the problem up to `limit`, e.g. use a limit of 2 to check
+/// whether a problem is well formed (has a unique solution).
+pub fn count_solutions(problem_opt: Option<SudokuCandidates>, limit: usize) -> SolutionCount {
+    match problem_opt {
+        Some(problem) => BacktrackingSolver.count_solutions(&problem, limit),
+        None => SolutionCount::Exactly(0),
     }
 }
 
@@ -584,6 +1203,23 @@ pub fn wasm_get_sample_sudoku_string(random_number: f64) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
+
+    /// Solution of the 9x9 problems in these tests that are built around the same grid.
+    const SOLUTION: &str =
+        "812753649943682175675491283154237896369845721287169534521974368438526917796318452";
+
+    /// Checks that the backtracking and the DLX solver both find `expected` as the only
+    /// solution of the problem.
+    fn assert_unique_solution(problem: &SudokuCandidates, expected: &str) {
+        for kind in &[SolverKind::Backtracking, SolverKind::Dlx] {
+            let solver = kind.solver();
+            assert!(solver.count_solutions(problem, 2).is_unique());
+            let solution = solver.solve(problem).unwrap();
+            assert!(solution.is_correct());
+            assert_eq!(solution.to_continuous_string(), expected);
+        }
+    }
 
     #[test]
     fn test_has_no_conflicts_js_interface() {
@@ -609,6 +1245,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_solve_with_solver_js_interface() {
+        assert_eq!(
+            wasm_solve_sudoku_with_solver(
+                "006037508700010900130050020002908000050020430600000090200005704003100060498600000",
+                "dlx"
+            ),
+            "926437518785216943134859627342968175859721436617543892261395784573184269498672351"
+        );
+    }
+
+    #[test]
+    fn test_unknown_names_js_interface() {
+        let problem_str =
+            "006037508700010900130050020002908000050020430600000090200005704003100060498600000";
+        assert_eq!(wasm_solve_sudoku_with_solver(problem_str, "magic"), "");
+        assert_eq!(
+            wasm_solve_sudoku_with_options(problem_str, "dlx", "magic"),
+            ""
+        );
+        assert!(wasm_sudoku_contains_conflicts_with_variant(
+            problem_str,
+            "magic"
+        ));
+        assert!(wasm_sudoku_contains_conflicts("0123"));
+        assert_eq!(wasm_solve_sudoku("0123"), "0123");
+    }
+
     #[test]
     fn test_unsolvable_solve_js_interface() {
         assert_eq!(
@@ -635,4 +1299,858 @@ mod tests {
         let sol_opt = solve_sudoku(SudokuCandidates::from_vec(sudoku_vec), 0);
         assert!(sol_opt.is_none());
     }
+
+    #[test]
+    fn test_search_restores_grid_on_backtracking() {
+        let sudoku_vec = convert_problem_str(
+            "000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        );
+        let problem = SudokuCandidates::from_vec(sudoku_vec).unwrap();
+        let mut search = BacktrackingSearch::new(problem.clone());
+        assert!(search.next_solution().unwrap().is_correct());
+        search.undo(0);
+        assert_eq!(search.problem.grid, problem.grid);
+    }
+
+    #[test]
+    fn test_solve_hard_problem() {
+        let sudoku_vec = convert_problem_str(
+            "800000000003600000070090200050007000000045700000100030001000068008500010090000400",
+        );
+        let solution = solve_sudoku(SudokuCandidates::from_vec(sudoku_vec), 0).unwrap();
+        assert_eq!(solution.to_continuous_string(), SOLUTION);
+    }
+
+    #[test]
+    fn test_count_solutions() {
+        assert_eq!(
+            count_solutions(
+                parse_sudoku(
+                    "006037508700010900130050020002908000050020430600000090200005704003100060498600000"
+                ),
+                2
+            ),
+            SolutionCount::Exactly(1)
+        );
+        assert_eq!(
+            count_solutions(parse_sudoku(&".".repeat(81)), 5),
+            SolutionCount::AtLeast(5)
+        );
+        assert_eq!(
+            count_solutions(
+                parse_sudoku(
+                    "066037508700010900130050020002908000050020430600000090200005704003100060498600000"
+                ),
+                2
+            ),
+            SolutionCount::Exactly(0)
+        );
+    }
+
+    #[test]
+    fn test_all_solutions_is_lazy_and_deterministic() {
+        let empty = || parse_sudoku(&"0".repeat(81));
+        let first: Vec<String> = all_solutions(empty())
+            .take(5)
+            .map(|sol| sol.to_continuous_string())
+            .collect();
+        assert_eq!(first.len(), 5);
+        assert_eq!(first.iter().collect::<HashSet<_>>().len(), 5);
+        for sol in &first {
+            assert!(parse_sudoku(sol).unwrap().is_correct());
+        }
+
+        let again: Vec<String> = all_solutions(empty())
+            .take(5)
+            .map(|sol| sol.to_continuous_string())
+            .collect();
+        assert_eq!(first, again);
+    }
+
+    #[test]
+    fn test_all_solutions_is_exhaustive() {
+        // a solved grid with some positions cleared again
+        let problem_str =
+            "926437518785216943134859627342968175859721436617543892261395784573184269498672351";
+        let mut ambiguous: Vec<char> = problem_str.chars().collect();
+        for pos_idx in [7, 8, 79, 80] {
+            ambiguous[pos_idx] = '0';
+        }
+        let ambiguous: String = ambiguous.into_iter().collect();
+        let solutions: Vec<SudokuCandidates> = all_solutions(parse_sudoku(&ambiguous)).collect();
+        assert!(solutions.iter().all(|sol| sol.is_correct()));
+        assert!(solutions
+            .iter()
+            .any(|sol| sol.to_continuous_string() == problem_str));
+        assert_eq!(
+            count_solutions(parse_sudoku(&ambiguous), 100),
+            SolutionCount::Exactly(solutions.len())
+        );
+        assert!(all_solutions(None).next().is_none());
+    }
+
+    #[test]
+    fn test_solve_4x4_problem() {
+        let problem_str = "1..4.4....4...21";
+        assert!(count_solutions(parse_sudoku(problem_str), 2).is_unique());
+        let solution = solve_sudoku(parse_sudoku(problem_str), 0).unwrap();
+        assert!(solution.is_correct());
+        assert_eq!(solution.to_continuous_string(), "1234341221434321");
+    }
+
+    #[test]
+    fn test_solve_6x6_problem_with_rectangular_cells() {
+        let problem = parse_sudoku("100000000200003000000040005000000006").unwrap();
+        assert_eq!(problem.layout().cell_rows(), 2);
+        assert_eq!(problem.layout().cell_cols(), 3);
+        let solution = solve_sudoku(Some(problem), 0).unwrap();
+        assert!(solution.is_correct());
+    }
+
+    #[test]
+    fn test_solve_12x12_problem_with_multi_digit_numbers() {
+        let mut problem_str = vec![String::new(); 144];
+        problem_str[0] = "12".to_owned();
+        problem_str[13] = "10".to_owned();
+        problem_str[143] = "1".to_owned();
+        let problem = parse_sudoku(&problem_str.join(",")).unwrap();
+        assert_eq!(problem.layout().size(), 12);
+        assert_eq!(problem.layout().cell_rows(), 3);
+        let solution = solve_sudoku(Some(problem), 0).unwrap();
+        assert!(solution.is_correct());
+        assert!(solution.to_continuous_string().starts_with('C'));
+    }
+
+    #[test]
+    fn test_16x16_letter_symbols() {
+        let problem_str = "123456789ABCDEFG".to_owned() + &".".repeat(240);
+        let problem = parse_sudoku(&problem_str).unwrap();
+        assert_eq!(problem.layout().size(), 16);
+        let solution = solve_sudoku(Some(problem), 0).unwrap();
+        assert!(solution.is_correct());
+        assert!(solution
+            .to_continuous_string()
+            .starts_with("123456789ABCDEFG"));
+        // letters beyond the grid size are treated as empty positions
+        assert_eq!(convert_problem_str("1H3.")[1], 0);
+    }
+
+    #[test]
+    fn test_unsupported_size() {
+        assert!(parse_sudoku("12345").is_none());
+    }
+
+    #[test]
+    fn test_diagonal_variant() {
+        let problem_str =
+            "003000000400000020700003050000201000600500000040000001090000605000060900000070000";
+        let diagonal: Variant = "diagonal".parse().unwrap();
+        // only unique with the additional diagonal constraints
+        assert!(!count_solutions(parse_sudoku(problem_str), 2).is_unique());
+        assert!(count_solutions(parse_sudoku_with_variant(problem_str, diagonal), 2).is_unique());
+
+        let expected =
+            "123456789456789123789123456935241867617538294842697531298314675371865942564972318";
+        for solver_name in SolverKind::NAMES.iter() {
+            assert_eq!(
+                wasm_solve_sudoku_with_options(problem_str, solver_name, "diagonal"),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_diagonal_conflicts() {
+        // two 1s on the main diagonal
+        let problem_str = "1".to_owned() + &"0".repeat(39) + "1" + &"0".repeat(40);
+        assert!(!wasm_sudoku_contains_conflicts(&problem_str));
+        assert!(wasm_sudoku_contains_conflicts_with_variant(
+            &problem_str,
+            "diagonal"
+        ));
+    }
+
+    const KILLER_PROBLEM: &str = "\
+000003000000002000000400000050000006000000700000000000000070000008000000700000050
+cage: 18 r1c1 r1c2 r2c1
+cage: 9 r1c3 r2c3 r2c2
+cage: 18 r3c1 r3c2 r3c3
+cage: 17 r1c4 r2c4 r3c4
+cage: 8 r1c5 r1c6
+cage: 11 r2c5 r2c6 r3c6
+cage: 9 r3c5
+cage: 10 r1c7 r1c8
+cage: 14 r1c9 r2c9
+cage: 3 r2c7 r3c7
+cage: 11 r3c8 r3c9
+cage: 7 r2c8
+cage: 19 r4c1 r4c2 r4c3 r5c3
+cage: 9 r5c1 r5c2
+cage: 17 r6c1 r6c2 r6c3
+cage: 13 r4c4 r4c5 r5c4
+cage: 16 r4c6 r5c6 r5c5
+cage: 16 r6c4 r6c5 r6c6
+cage: 20 r4c7 r5c7 r6c7
+cage: 15 r4c8 r4c9
+cage: 7 r5c8 r5c9 r6c9
+cage: 3 r6c8
+cage: 7 r7c1 r7c2
+cage: 9 r7c3 r8c3
+cage: 11 r8c1 r9c1
+cage: 15 r9c2 r9c3
+cage: 3 r8c2
+cage: 26 r7c4 r7c5 r7c6 r8c6
+cage: 7 r8c4 r8c5
+cage: 12 r9c4 r9c5 r9c6
+cage: 18 r7c7 r7c8 r8c7
+cage: 16 r7c9 r8c9 r8c8
+cage: 11 r9c7 r9c8 r9c9";
+
+    #[test]
+    fn test_killer_cages() {
+        // only unique with the cages
+        let grid = KILLER_PROBLEM.lines().next().unwrap();
+        assert!(!count_solutions(parse_sudoku(grid), 2).is_unique());
+        let problem = parse_sudoku(KILLER_PROBLEM).unwrap();
+        // the single position cage is placed, the cage of 3 in two positions is 1 + 2
+        assert_eq!(problem.get(22), Candidates::single(9));
+        let one_two = Candidates::single(1) | Candidates::single(2);
+        assert!(problem.get(15).without(one_two).is_empty());
+        assert_unique_solution(&problem, SOLUTION);
+    }
+
+    #[test]
+    fn test_killer_cage_conflicts() {
+        let grid = "89".to_owned() + &"0".repeat(79);
+        assert!(parse_sudoku(&(grid.clone() + "\ncage: 17 r1c1 r1c2")).is_some());
+        assert!(parse_sudoku(&(grid.clone() + "\ncage: 16 r1c1 r1c2")).is_none());
+        // 8 + 9 already exceeds the sum of the cage
+        assert!(parse_sudoku(&(grid + "\ncage: 16 r1c1 r1c2 r1c3")).is_none());
+    }
+
+    #[test]
+    fn test_parse_errors_and_conflicts() {
+        let grid = "0".repeat(81);
+        let parse =
+            |problem_str: &str| try_parse_sudoku_with_variant(problem_str, Variant::default());
+        assert!(parse(&grid).unwrap().is_some());
+        assert!(parse(&("11".to_owned() + &grid[2..])).unwrap().is_none());
+        assert!(parse("123").is_err());
+        assert!(parse("").is_err());
+        assert!(parse(&(grid.clone() + "\ncage: 18 r1c1 r1c2")).is_err());
+        assert!(parse(&(grid + "\nfoo: 3 r1c1")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_cages() {
+        let grid = "0".repeat(81);
+        // no two distinct digits add up to 18
+        assert!(parse_sudoku(&(grid.clone() + "\ncage: 18 r1c1 r1c2")).is_none());
+        // 1 + 2 is the smallest sum of two distinct digits
+        assert!(parse_sudoku(&(grid.clone() + "\ncage: 2 r1c1 r1c2")).is_none());
+        assert!(parse_sudoku(&(grid.clone() + "\ncage: 0")).is_none());
+        assert!(parse_sudoku(&(grid.clone() + "\ncage: 10 r1c1 r10c1")).is_none());
+        assert!(parse_sudoku(&(grid.clone() + "\ncage: r1c1 r1c2")).is_none());
+        assert!(parse_sudoku(&(grid + "\nfoo: 3 r1c1")).is_none());
+    }
+
+    #[test]
+    fn test_jigsaw_regions() {
+        let grid =
+            "003000080500000000900007010000002000060100005070000900010000006000043000007010000";
+        let problem_str = grid.to_owned()
+            + "\nregions: DABBBCCCC DAAABBBCC DAAEBBFCC DDAEBFFCF DDAEEEFFF \
+               DDAEHEIIF GGGGHEIIF GGGGHEIII GHHHHHHII";
+        // only unique with the regions instead of the cells
+        assert!(!count_solutions(parse_sudoku(grid), 2).is_unique());
+        let problem = parse_sudoku(&problem_str).unwrap();
+        assert!(problem
+            .layout()
+            .houses()
+            .iter()
+            .all(|house| !matches!(house.kind, HouseKind::Cell(_))));
+        let expected =
+            "123456789589761234936587412341692857264178395875234961718329546652943178497815623";
+        assert_unique_solution(&problem, expected);
+
+        // r1c1 and r4c2 are in different rows, columns and cells, but both in region D
+        let ones = "1".to_owned() + &"0".repeat(27) + "1" + &"0".repeat(52);
+        let regions = &problem_str[81..];
+        assert!(parse_sudoku(&ones).is_some());
+        assert!(parse_sudoku(&(ones + regions)).is_none());
+    }
+
+    #[test]
+    fn test_invalid_regions() {
+        let grid = "0".repeat(81);
+        // the cells as regions are fine, but not a region map of the wrong length
+        let cells = "AAABBBCCC ".repeat(3) + &"DDDEEEFFF ".repeat(3) + &"GGGHHHIII ".repeat(3);
+        assert!(parse_sudoku(&format!("{}\nregions: {}", grid, cells)).is_some());
+        assert!(parse_sudoku(&format!("{}\nregions: {}", grid, &cells[1..])).is_none());
+        // A with 10 and B with 8 positions
+        let uneven = cells.replacen('B', "A", 1);
+        assert!(parse_sudoku(&format!("{}\nregions: {}", grid, uneven)).is_none());
+        // ten regions
+        let ten = cells.replacen('A', "J", 1).replacen('B', "A", 1);
+        assert!(parse_sudoku(&format!("{}\nregions: {}", grid, ten)).is_none());
+    }
+
+    #[test]
+    fn test_anti_knight_variant() {
+        let problem_str =
+            "003000000000000000400008000000003000030600008090000102010000007000000900000030000";
+        let anti_knight: Variant = "anti-knight".parse().unwrap();
+        assert!(!count_solutions(parse_sudoku(problem_str), 2).is_unique());
+        assert!(
+            count_solutions(parse_sudoku_with_variant(problem_str, anti_knight), 2).is_unique()
+        );
+
+        let expected =
+            "123456789987312456465798213251983674734621598896574132512849367378265941649137825";
+        for solver_name in SolverKind::NAMES.iter() {
+            assert_eq!(
+                wasm_solve_sudoku_with_options(problem_str, solver_name, "anti-knight"),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_anti_king_variant_per_problem() {
+        let problem_str = "\
+103000000700000040400007030010504000070900008090000000000000903000040000008070060
+variant: anti-king";
+        let problem = parse_sudoku(problem_str).unwrap();
+        assert!(problem.layout().variant().anti_king);
+        // the 3 in r1c3 touches r2c4 diagonally, across the border of the cells
+        assert!(!problem.get(12).contains(3));
+        assert!(parse_sudoku(problem_str.lines().next().unwrap())
+            .unwrap()
+            .get(12)
+            .contains(3));
+        assert_unique_solution(
+            &problem,
+            "123456789785293146469187235812534697374962518596718324641825973937641852258379461",
+        );
+        assert!(parse_sudoku(&("0".repeat(81) + "\nvariant: anti-queen")).is_none());
+    }
+
+    #[test]
+    fn test_anti_move_conflicts() {
+        // two 1s a knight's move apart
+        let problem_str = "001".to_owned() + &"0".repeat(10) + "1" + &"0".repeat(67);
+        assert!(!wasm_sudoku_contains_conflicts(&problem_str));
+        assert!(wasm_sudoku_contains_conflicts_with_variant(
+            &problem_str,
+            "anti-knight"
+        ));
+        assert!(!wasm_sudoku_contains_conflicts_with_variant(
+            &problem_str,
+            "anti-king"
+        ));
+    }
+
+    #[test]
+    fn test_thermometers() {
+        let problem_str = "\
+002000000000000000600000000000200000000000000000000004000000000000000000000000000
+thermo: r7c3 r7c2 r8c2 r8c1 r7c1 r6c2 r5c3
+thermo: r3c9 r2c9 r2c8 r3c8 r4c8
+thermo: r5c9 r5c8 r6c8 r6c9 r7c8 r8c9 r7c9
+thermo: r1c6 r1c5 r2c4 r1c4 r2c5 r3c5
+thermo: r8c4 r9c3 r8c3 r9c2
+thermo: r2c7 r3c7 r4c6 r4c7
+thermo: r5c6 r6c5 r7c5 r7c4
+thermo: r6c1 r5c1 r4c2 r5c2 r6c3 r5c4";
+        let problem = parse_sudoku(problem_str).unwrap();
+        // the bulb of a thermometer of seven positions leaves room for six larger digits
+        assert!(problem.get(56).last().unwrap() <= 3);
+        assert_unique_solution(&problem, SOLUTION);
+    }
+
+    #[test]
+    fn test_thermometer_conflicts() {
+        let grid = "0".repeat(81);
+        // 9 in the middle of a thermometer, 2 before a 1
+        assert!(parse_sudoku(&(grid.clone() + "\nthermo: r1c1 r1c2 r1c3")).is_some());
+        let nine = "09".to_owned() + &"0".repeat(79);
+        assert!(parse_sudoku(&(nine + "\nthermo: r1c1 r1c2 r1c3")).is_none());
+        let decreasing = "21".to_owned() + &"0".repeat(79);
+        assert!(parse_sudoku(&(decreasing + "\nthermo: r1c1 r1c2")).is_none());
+        assert!(parse_sudoku(&(grid + "\nthermo: r1c1")).is_none());
+    }
+
+    #[test]
+    fn test_kropki_dots_with_negative_constraint() {
+        let problem_str = "\
+000000000000000000000000000000000000
+white: r1c1 r2c1, r1c2 r1c3, r1c5 r1c6, r2c1 r2c2, r2c2 r2c3, r2c4 r2c5, r2c5 r2c6, \
+       r3c1 r3c2, r3c2 r3c3, r3c3 r4c3, r3c4 r3c5, r3c5 r4c5, r4c1 r4c2, r4c4 r4c5, \
+       r5c1 r5c2, r5c2 r6c2, r5c4 r5c5, r5c5 r5c6, r6c2 r6c3, r6c5 r6c6
+black: r1c3 r1c4, r2c3 r3c3, r2c5 r3c5, r2c6 r3c6, r4c2 r5c2, r4c3 r4c4, r5c4 r6c4, r5c6 r6c6
+negative: white, black";
+        let problem = parse_sudoku(problem_str).unwrap();
+        // 5 has neither half nor double among 1 to 6
+        assert!(!problem.get(2).contains(5));
+        assert_unique_solution(&problem, "312465456123123546564231231654645312");
+    }
+
+    #[test]
+    fn test_xv_markers() {
+        let grid = "0".repeat(81);
+        let problem = parse_sudoku(&(grid.clone() + "\nv: r1c1 r1c2\nx: r1c1 r2c1")).unwrap();
+        // 1 + 4 or 2 + 3, and the partner for 10 has to be 9, 8, 7 or 6
+        assert_eq!(problem.get(0).iter().collect::<Vec<u8>>(), vec![1, 2, 3, 4]);
+        assert_eq!(problem.get(9).iter().collect::<Vec<u8>>(), vec![6, 7, 8, 9]);
+        // without markers, no adjacent digits may add up to 5
+        let negative = parse_sudoku(&("1".to_owned() + &"0".repeat(80) + "\nnegative: v")).unwrap();
+        assert!(!negative.get(1).contains(4));
+        assert!(negative.get(10).contains(4));
+
+        let conflict = "14".to_owned() + &"0".repeat(79);
+        assert!(parse_sudoku(&(conflict.clone() + "\nv: r1c1 r1c2")).is_some());
+        assert!(parse_sudoku(&(conflict.clone() + "\nx: r1c1 r1c2")).is_none());
+        assert!(parse_sudoku(&(conflict.clone() + "\nnegative: v")).is_none());
+        // the marker exempts the pair from the negative constraint, in any order
+        assert!(parse_sudoku(&(conflict + "\nnegative: v\nv: r1c1 r1c2")).is_some());
+        // only adjacent positions can be marked
+        assert!(parse_sudoku(&(grid.clone() + "\nx: r1c1 r2c2")).is_none());
+        assert!(parse_sudoku(&(grid + "\nx: r1c1")).is_none());
+    }
+
+    #[test]
+    fn test_sandwich_clues() {
+        let problem_str = "\
+002000000000000000000000000000000000000000000080000000000000000000000900000000000
+sandwich-rows: 27 23 0 29 26 6 0 0 9
+sandwich-columns: 6 35 7 0 22 12 25 11 14";
+        let problem = parse_sudoku(problem_str).unwrap();
+        assert_unique_solution(&problem, SOLUTION);
+
+        // a single clue of 0: 1 and 9 are next to each other in the third row
+        let partial = "0".repeat(81) + "\nsandwich-rows: . . 0 - . . . . .";
+        let solution = solve_sudoku(parse_sudoku(&partial), 0).unwrap();
+        let third_row: Vec<u8> = (18..27)
+            .map(|pos_idx| solution.get(pos_idx).first().unwrap())
+            .collect();
+        let one = third_row.iter().position(|digit| *digit == 1).unwrap();
+        let nine = third_row.iter().position(|digit| *digit == 9).unwrap();
+        assert_eq!(one.abs_diff(nine), 1);
+
+        let grid = "0".repeat(81);
+        assert!(parse_sudoku(&(grid.clone() + "\nsandwich-rows: 0 0")).is_none());
+        assert!(parse_sudoku(&(grid.clone() + "\nsandwich-rows: 36 . . . . . . . .")).is_none());
+        assert!(parse_sudoku(&(grid + "\nsandwich-columns: a . . . . . . . .")).is_none());
+    }
+
+    const ARROW_PROBLEM: &str = "\
+002700000000000000000000000050007000000800000000000000000000060000000000006000000
+arrow: r9c2 > r8c1 r7c1
+arrow: r8c9 > r9c8 r9c9
+arrow: r2c8 > r3c7 r2c6 r1c6
+arrow: r5c3 > r6c4 r7c3 r7c2 r6c1 r5c1
+arrow: r3c8 > r3c9 r2c9
+arrow: r4c7 > r5c6 r4c5
+arrow: r7c8 > r6c9 r5c8
+arrow: r7c9 > r8c8 r7c7 r7c6
+arrow: r3c5 > r4c6 r3c6 r2c7
+arrow: r4c1 r4c2 > r4c3 r3c3 r2c4";
+
+    #[test]
+    fn test_arrows() {
+        let grid = ARROW_PROBLEM.lines().next().unwrap();
+        assert!(!count_solutions(parse_sudoku(grid), 2).is_unique());
+        let problem = parse_sudoku(ARROW_PROBLEM).unwrap();
+        // five shaft positions add up to at least 5
+        assert!(!problem.get(38).contains(4));
+        assert_unique_solution(&problem, SOLUTION);
+    }
+
+    #[test]
+    fn test_is_correct_checks_arrows() {
+        let solution = parse_sudoku(SOLUTION).unwrap();
+        assert!(solution.is_correct());
+        // 8 in the circle, 1 + 2 on the shaft
+        let layout = solution
+            .layout()
+            .clone()
+            .with_arrow(vec![0], vec![1, 2])
+            .unwrap();
+        let wrong = SudokuCandidates {
+            layout: Arc::new(layout),
+            grid: solution.grid.clone(),
+            initial: Arc::clone(&solution.initial),
+        };
+        assert!(!wrong.is_correct());
+        // with r1c5 added to the shaft: 8 = 1 + 2 + 5
+        let layout = solution
+            .layout()
+            .clone()
+            .with_arrow(vec![0], vec![1, 2, 4])
+            .unwrap();
+        let right = SudokuCandidates {
+            layout: Arc::new(layout),
+            grid: solution.grid,
+            initial: solution.initial,
+        };
+        assert!(right.is_correct());
+    }
+
+    #[test]
+    fn test_invalid_arrows() {
+        let grid = "0".repeat(81);
+        assert!(parse_sudoku(&(grid.clone() + "\narrow: r1c1 > r1c2 r1c3")).is_some());
+        assert!(parse_sudoku(&(grid.clone() + "\narrow: r1c1 r1c2 r1c3")).is_none());
+        assert!(parse_sudoku(&(grid.clone() + "\narrow: r1c1 >")).is_none());
+        assert!(parse_sudoku(&(grid + "\narrow: r1c1 > r1c1 r1c2")).is_none());
+        // the shaft cannot reach 1
+        let one = "1".to_owned() + &"0".repeat(80);
+        assert!(parse_sudoku(&(one + "\narrow: r1c1 > r1c2 r2c1")).is_none());
+    }
+
+    const GREATER_PROBLEM: &str = "\
+000000000000000000600000000100000000000000000000000000000000000000000000000000000
+greater-rows: ><.>>.>< >>.<>.<> <>.<>.<> <>.<<.<> <<.><.>> <>.<<.>< >>.>>.<< ><.><.>< <>.><.<>
+greater-columns: <>.<>.>< <<.<<.<< <<.<>.<> >>.<>.>> <<.<<.>> >>.><.<< ><.>>.<> <<.><.>< >>.><.>>";
+
+    #[test]
+    fn test_greater_than() {
+        let grid = GREATER_PROBLEM.lines().next().unwrap();
+        assert!(!count_solutions(parse_sudoku(grid), 2).is_unique());
+        let problem = parse_sudoku(GREATER_PROBLEM).unwrap();
+        // r1c1 > r1c2 < r1c3 and r1c1 above r2c1, which is larger
+        assert!(!problem.get(0).contains(1));
+        assert!(!problem.get(1).contains(9));
+        assert!(!problem.get(9).contains(1));
+        assert_unique_solution(&problem, SOLUTION);
+    }
+
+    #[test]
+    fn test_invalid_inequalities() {
+        let grid = "0".repeat(81);
+        let no_signs = ["........"; 9].join(" ");
+        assert!(parse_sudoku(&format!("{}\ngreater-rows: {}", grid, no_signs)).is_some());
+        assert!(parse_sudoku(&format!("{}\ngreater-rows: {} .", grid, no_signs)).is_none());
+        let wrong_sign = no_signs.replacen('.', "^", 1);
+        assert!(parse_sudoku(&format!("{}\ngreater-rows: {}", grid, wrong_sign)).is_none());
+        let short = no_signs.replacen('.', "", 1);
+        assert!(parse_sudoku(&format!("{}\ngreater-columns: {}", grid, short)).is_none());
+        // 1 > 2 in the first row, 1 < r2c1 in the first column
+        let one_two = "12".to_owned() + &"0".repeat(79);
+        let greater = no_signs.replacen('.', ">", 1);
+        let less = no_signs.replacen('.', "<", 1);
+        assert!(parse_sudoku(&format!("{}\ngreater-rows: {}", one_two, greater)).is_none());
+        assert!(parse_sudoku(&format!("{}\ngreater-columns: {}", one_two, less)).is_some());
+        assert!(parse_sudoku(&format!("{}\ngreater-columns: {}", one_two, greater)).is_none());
+    }
+
+    const EVEN_ODD_PROBLEM: &str = "\
+000700000903080070600090200154200800009040000000000500000900000008000000000000000
+even: r1c8 r4c9 r5c4 r5c8 r7c2 r7c6 r8c1 r8c5
+odd: r1c4 r2c3 r2c7 r3c2 r3c6 r4c1 r4c5 r6c3 r6c7 r8c9 r9c4 r9c8";
+
+    #[test]
+    fn test_even_odd() {
+        let grid = EVEN_ODD_PROBLEM.lines().next().unwrap();
+        assert!(!count_solutions(parse_sudoku(grid), 2).is_unique());
+        let problem = parse_sudoku(EVEN_ODD_PROBLEM).unwrap();
+        // even, without the 2 in its cell
+        assert_eq!(problem.get(7).iter().collect::<Vec<u8>>(), vec![4, 6, 8]);
+        assert_unique_solution(&problem, SOLUTION);
+
+        // the solution violates a low marker in the top left corner
+        let solution = parse_sudoku(SOLUTION).unwrap();
+        let layout = solution
+            .layout()
+            .clone()
+            .with_restriction(vec![0], named_digits("low", 9).unwrap())
+            .unwrap();
+        let wrong = SudokuCandidates {
+            layout: Arc::new(layout),
+            grid: solution.grid,
+            initial: solution.initial,
+        };
+        assert!(!wrong.is_correct());
+        assert!(parse_sudoku(&(SOLUTION.to_owned() + "\nhigh: r1c1")).is_some());
+        assert!(parse_sudoku(&(SOLUTION.to_owned() + "\nlow: r1c1")).is_none());
+    }
+
+    #[test]
+    fn test_subsets_cut_search() {
+        // needs a naked and a hidden pair besides singles, searching without the subsets
+        // takes 44 guesses
+        let problem = parse_sudoku(
+            "012050009000002000075090000100000090300840001000000530000900068430000010090308000",
+        )
+        .unwrap();
+        let mut search = BacktrackingSearch::new(problem);
+        assert!(search.next_solution().unwrap().is_correct());
+        assert_eq!(search.stats, SolveStats::default());
+    }
+
+    #[test]
+    fn test_pencil_marks() {
+        let grid = "0".repeat(81);
+        let problem = parse_sudoku(&(grid.clone() + "\ncandidates: 12 r1c1 r1c2")).unwrap();
+        assert_eq!(problem.get(1).iter().collect::<Vec<u8>>(), vec![1, 2]);
+        assert!(parse_sudoku(&(grid.clone() + "\ncandidates: 1 r1c1 r1c2")).is_none());
+        assert!(parse_sudoku(&(grid.clone() + "\ncandidates: 1.3 r1c1")).is_none());
+        assert!(parse_sudoku(&(grid + "\ncandidates:")).is_none());
+
+        // a pencil-marked grid with the digit of the solution and its successor everywhere
+        let layout = Arc::new(Layout::for_size(9).unwrap());
+        let candidates: Vec<Candidates> = SOLUTION
+            .bytes()
+            .map(|symbol| symbol - b'0')
+            .map(|digit| Candidates::single(digit) | Candidates::single(digit % 9 + 1))
+            .collect();
+        let problem = SudokuCandidates::from_candidates(Arc::clone(&layout), candidates).unwrap();
+        let solution = solve_sudoku(Some(problem), 0).unwrap();
+        assert_eq!(solution.to_continuous_string(), SOLUTION);
+        let mut candidates = vec![Candidates::all(9); 81];
+        candidates[0] = [1, 2].iter().copied().collect();
+        candidates[1] = Candidates::single(1);
+        candidates[2] = Candidates::single(2);
+        assert!(SudokuCandidates::from_candidates(Arc::clone(&layout), candidates).is_none());
+        assert!(SudokuCandidates::from_candidates(Arc::clone(&layout), vec![]).is_none());
+
+        // digits outside of the grid size are rejected instead of overflowing the candidates
+        let mut numbers = vec![0; 81];
+        numbers[0] = 10;
+        assert!(
+            SudokuCandidates::from_vec_with_layout(Arc::clone(&layout), numbers.clone()).is_none()
+        );
+        numbers[0] = 40;
+        assert!(SudokuCandidates::from_vec_with_layout(layout, numbers).is_none());
+    }
+
+    #[test]
+    fn test_hyper_variant() {
+        let problem_str =
+            "002000080300000020700005040000007000050400000060090000030000902000000600008070000";
+        let hyper: Variant = "hyper".parse().unwrap();
+        assert!(!count_solutions(parse_sudoku(problem_str), 2).is_unique());
+        assert!(count_solutions(parse_sudoku_with_variant(problem_str, hyper), 2).is_unique());
+        let expected =
+            "512346789346789125789125346923517864857463291164298537631854972475932618298671453";
+        for solver_name in SolverKind::NAMES.iter() {
+            assert_eq!(
+                wasm_solve_sudoku_with_options(problem_str, solver_name, "hyper"),
+                expected
+            );
+        }
+
+        // the same windows declared as extra houses
+        let with_houses = problem_str.to_owned()
+            + "
+house: r2c2 r2c3 r2c4 r3c2 r3c3 r3c4 r4c2 r4c3 r4c4
+house: r2c6 r2c7 r2c8 r3c6 r3c7 r3c8 r4c6 r4c7 r4c8
+house: r6c2 r6c3 r6c4 r7c2 r7c3 r7c4 r8c2 r8c3 r8c4
+house: r6c6 r6c7 r6c8 r7c6 r7c7 r7c8 r8c6 r8c7 r8c8";
+        let problem = parse_sudoku(&with_houses).unwrap();
+        assert_eq!(problem.layout().houses().len(), 31);
+        assert_eq!(
+            solve_sudoku(Some(problem), 0)
+                .unwrap()
+                .to_continuous_string(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_extra_house_conflicts() {
+        // two 1s on the main diagonal, declared as an extra house
+        let problem_str = "1".to_owned() + &"0".repeat(39) + "1" + &"0".repeat(40);
+        let diagonal = "\nhouse: r1c1 r2c2 r3c3 r4c4 r5c5 r6c6 r7c7 r8c8 r9c9";
+        assert!(parse_sudoku(&problem_str).is_some());
+        assert!(parse_sudoku(&(problem_str.clone() + diagonal)).is_none());
+        assert!(parse_sudoku(&(problem_str + "\nhouse: r1c1 r2c2")).is_none());
+    }
+
+    // the five grids of a samurai sudoku: top left, top right, center, bottom left, bottom right
+    const SAMURAI_PROBLEM: &str = concat!(
+        "..57.2.....368.4......4....2...9.8...6.2......9...8.4.5........8...7............1",
+        "....4.7.96...8.1..8..1.7.5.4......6.5.78......6......2............4....3......5..",
+        "....7.......1.......1............8......91..269.....................2..19...8....",
+        ".3...9...............23.9......7...97...15.....53...2..1.5..7...2...1.4.9........",
+        "...........1.7...9....6...8.3...59.2......6...8....5.74...8....7..63..2........6.",
+    );
+
+    const SAMURAI_SOLUTION: &str = concat!(
+        "645712398123689457789345126251496873468237915397158642574861239812973564936524781",
+        "351246789672589134894137256413792865527864391968351472145673928789425613236918547",
+        "239678145564123789781459236125764893347891562698235417452316978873942651916587324",
+        "136789452249156873578234916361472589782915634495368127614523798827691345953847261",
+        "978123456651478239324569178136745982547892613289316547463281795795634821812957364",
+    );
+
+    #[test]
+    fn test_samurai() {
+        let problem = parse_sudoku(SAMURAI_PROBLEM).unwrap();
+        assert_eq!(problem.layout().grids().len(), 5);
+        assert_eq!(problem.layout().num_positions(), 5 * 81 - 4 * 9);
+        assert_unique_solution(&problem, SAMURAI_SOLUTION);
+        assert_eq!(
+            wasm_solve_sudoku_with_solver(SAMURAI_PROBLEM, "dlx"),
+            SAMURAI_SOLUTION
+        );
+    }
+
+    #[test]
+    fn test_samurai_shared_positions() {
+        // the bottom right cell of the top left grid is the top left cell of the center grid,
+        // so a digit given in one of them eliminates it from the rows of both grids
+        let mut fields = "0".repeat(405);
+        fields.replace_range(80..81, "5");
+        let problem = parse_sudoku(&fields).unwrap();
+        let layout = problem.layout();
+        assert_eq!(layout.grids()[0][80], layout.grids()[2][20]);
+        assert_eq!(layout.row_col(layout.grids()[2][20]), (8, 8));
+        assert!(!problem.get(layout.pos_idx(8, 14).unwrap()).contains(5));
+        assert!(problem.get(layout.pos_idx(9, 9).unwrap()).contains(5));
+        assert_eq!(&problem.to_continuous_string()[162 + 20..163 + 20], "5");
+
+        // the shared position is given in both grids
+        let mut both = fields.clone();
+        both.replace_range(162 + 20..163 + 20, "5");
+        assert!(parse_sudoku(&both).is_some());
+        both.replace_range(162 + 20..163 + 20, "6");
+        assert!(parse_sudoku(&both).is_none());
+
+        // rules of a single grid
+        assert!(parse_sudoku(&(fields.clone() + "\nsandwich-rows: 10 . . . . . . . .")).is_none());
+        assert!(parse_sudoku(&(fields + "\ncage: 3 r1c1 r1c2")).is_some());
+
+        // a 9x9 problem one field short is not mistaken for a samurai of 4x4 grids
+        let mut short = "0".repeat(80);
+        short.replace_range(0..1, "7");
+        assert!(parse_sudoku(&short).is_none());
+    }
+
+    /// German whispers: neighbors along the line differ by at least 5.
+    #[derive(Debug)]
+    struct Whisper {
+        positions: Vec<usize>,
+    }
+
+    impl Whisper {
+        fn neighbors(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+            self.positions
+                .windows(2)
+                .flat_map(|pair| [(pair[0], pair[1]), (pair[1], pair[0])])
+        }
+    }
+
+    impl Constraint for Whisper {
+        fn positions(&self) -> &[usize] {
+            &self.positions
+        }
+
+        fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+            self.neighbors().any(|(pos_idx, other_idx)| {
+                let (cands, other) = (problem.get(pos_idx), problem.get(other_idx));
+                cands.len() == 1
+                    && other.len() == 1
+                    && cands.first().unwrap().abs_diff(other.first().unwrap()) < 5
+            })
+        }
+
+        fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+            self.neighbors()
+                .filter_map(|(pos_idx, other_idx)| {
+                    let other = problem.get(other_idx);
+                    let ruled_out = problem
+                        .get(pos_idx)
+                        .iter()
+                        .filter(|digit| other.iter().all(|o| digit.abs_diff(o) < 5))
+                        .fold(Candidates::NONE, |ruled_out, digit| {
+                            ruled_out | Candidates::single(digit)
+                        });
+                    if ruled_out.is_empty() {
+                        None
+                    } else {
+                        Some(Elimination {
+                            pos_idx,
+                            candidates: ruled_out,
+                        })
+                    }
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_user_defined_constraint() {
+        let whisper = Whisper {
+            positions: vec![0, 1, 2, 11],
+        };
+        let layout = Layout::for_size(9)
+            .unwrap()
+            .with_constraint(whisper)
+            .unwrap();
+        let layout = Arc::new(layout);
+        let problem =
+            SudokuCandidates::from_vec_with_layout(Arc::clone(&layout), vec![0; 81]).unwrap();
+        assert!((0..3).all(|pos_idx| !problem.get(pos_idx).contains(5)));
+        assert!(problem.get(3).contains(5));
+
+        for kind in &[SolverKind::Backtracking, SolverKind::Dlx] {
+            let solution = kind.solver().solve(&problem).unwrap();
+            assert!(solution.is_correct());
+            assert!(layout.constraints()[0]
+                .positions()
+                .windows(2)
+                .all(|pair| solution
+                    .get(pair[0])
+                    .first()
+                    .unwrap()
+                    .abs_diff(solution.get(pair[1]).first().unwrap())
+                    >= 5));
+        }
+
+        // r1c1 and r1c2 are 1 and 2
+        let mut numbers = vec![0; 81];
+        numbers[0] = 1;
+        numbers[1] = 2;
+        assert!(SudokuCandidates::from_vec_with_layout(layout, numbers).is_none());
+        let outside = Whisper {
+            positions: vec![80, 81],
+        };
+        assert!(Layout::for_size(9)
+            .unwrap()
+            .with_constraint(outside)
+            .is_err());
+    }
+
+    #[test]
+    fn test_candidates_bitmask() {
+        let mut cands = Candidates::all(9);
+        assert_eq!(cands.len(), 9);
+        cands.remove(1);
+        cands.remove(5);
+        assert_eq!(cands.len(), 7);
+        assert!(!cands.contains(5));
+        assert_eq!(cands.first(), Some(2));
+        assert_eq!(cands.iter().collect::<Vec<u8>>(), vec![2, 3, 4, 6, 7, 8, 9]);
+        assert_eq!(cands.last(), Some(9));
+        assert_eq!(Candidates::NONE.last(), None);
+        assert_eq!(
+            Candidates::range(3, 5).iter().collect::<Vec<u8>>(),
+            vec![3, 4, 5]
+        );
+        assert!(Candidates::range(5, 4).is_empty());
+        assert!((Candidates::single(1) & cands).is_empty());
+    }
+
+    #[test]
+    fn test_from_vec_propagates_singles() {
+        let sudoku_vec = convert_problem_str(
+            "006037508700010900130050020002908000050020430600000090200005704003100060498600000",
+        );
+        let problem = SudokuCandidates::from_vec(sudoku_vec).unwrap();
+        assert!(!problem.has_unresolvable_conflicts());
+        // row 0 contains 6, 3, 7, 5, 8 so these are eliminated from the empty position (0, 0)
+        assert!(!problem.get(0).contains(6));
+        assert!(problem.get(0).len() <= 4);
+    }
 }
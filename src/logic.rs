@@ -0,0 +1,644 @@
+//! Human-style solving: techniques are applied one at a time, each deduction is recorded as a
+//! step. Guessing is only used once no technique applies and is marked as such in the log.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::constraint::Elimination;
+use crate::fish::find_fish;
+use crate::intersections::find_locked_candidates;
+use crate::layout::Layout;
+use crate::solver::{BacktrackingSolver, SolutionCount, SolveStats, Solver};
+use crate::subsets::find_subset;
+use crate::wings::find_wing;
+use crate::{Candidates, SudokuCandidates};
+
+/// Reasoning behind a step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Technique {
+    /// The position has a single candidate left.
+    NakedSingle,
+    /// The position is the only one of a house that can hold the digit.
+    HiddenSingle,
+    /// Candidates ruled out by a rule in addition to the houses, e.g. a killer cage.
+    Constraint,
+    /// The given number of positions of a house have as many candidates in total.
+    NakedSubset(usize),
+    /// The given number of digits fit into as many positions of a house only.
+    HiddenSubset(usize),
+    /// The digit of a box can only go into positions of one row or column.
+    Pointing,
+    /// The digit of a row or column can only go into positions of one box.
+    Claiming,
+    /// The digit of `size` rows can only go into `size` columns or the other way round,
+    /// apart from the fins, which are all in one box. A sashimi fish has a base set with
+    /// a single candidate in the cover sets.
+    Fish {
+        size: usize,
+        fins: Vec<usize>,
+        sashimi: bool,
+    },
+    /// A bivalue pivot sees two bivalue pincers, positions seeing both pincers cannot hold
+    /// their common digit. The positions are the pivot followed by the pincers.
+    XyWing,
+    /// Like `XyWing` with a pivot of three candidates, positions seeing the pivot as well
+    /// lose the common digit.
+    XyzWing,
+    /// Two positions with the same two candidates, connected by a strong link on one of them.
+    /// The positions are the two followed by the link, the house of the link is reported.
+    WWing,
+    /// No technique applies, the digit of the solution found by the backtracking solver is
+    /// placed. The log therefore never contains a wrong guess.
+    Guess,
+}
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Technique::NakedSingle => write!(f, "naked single"),
+            Technique::HiddenSingle => write!(f, "hidden single"),
+            Technique::Constraint => write!(f, "constraint"),
+            Technique::NakedSubset(size) => write!(f, "naked {}", subset_name(*size)),
+            Technique::HiddenSubset(size) => write!(f, "hidden {}", subset_name(*size)),
+            Technique::Pointing => write!(f, "pointing"),
+            Technique::Claiming => write!(f, "claiming"),
+            Technique::Fish {
+                size,
+                fins,
+                sashimi,
+            } => {
+                let name = match size {
+                    2 => "X-Wing".to_owned(),
+                    3 => "Swordfish".to_owned(),
+                    4 => "Jellyfish".to_owned(),
+                    _ => format!("fish of size {}", size),
+                };
+                match (fins.is_empty(), sashimi) {
+                    (true, _) => write!(f, "{}", name),
+                    (false, false) => write!(f, "finned {}", name),
+                    (false, true) => write!(f, "sashimi {}", name),
+                }
+            }
+            Technique::XyWing => write!(f, "XY-Wing"),
+            Technique::XyzWing => write!(f, "XYZ-Wing"),
+            Technique::WWing => write!(f, "W-Wing"),
+            Technique::Guess => write!(f, "guess"),
+        }
+    }
+}
+
+fn subset_name(size: usize) -> String {
+    match size {
+        2 => "pair".to_owned(),
+        3 => "triple".to_owned(),
+        4 => "quad".to_owned(),
+        _ => format!("subset of {}", size),
+    }
+}
+
+/// Outcome of a step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// The digit is placed, which removes it from the candidates of all peers.
+    Place { pos_idx: usize, digit: u8 },
+    /// The candidates are removed.
+    Eliminate(Vec<Elimination>),
+}
+
+/// A single deduction of the logical solver.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub technique: Technique,
+    /// Positions the deduction is based on.
+    pub positions: Vec<usize>,
+    /// Indices into `Layout::houses` of the houses the deduction is based on.
+    pub houses: Vec<usize>,
+    pub action: Action,
+}
+
+impl Step {
+    /// Base sets of a fish, indices into `Layout::houses`. Empty for other techniques.
+    pub fn base_sets(&self) -> &[usize] {
+        match self.technique {
+            Technique::Fish { size, .. } => &self.houses[..size],
+            _ => &[],
+        }
+    }
+
+    /// Cover sets of a fish, indices into `Layout::houses`. Empty for other techniques.
+    pub fn cover_sets(&self) -> &[usize] {
+        match self.technique {
+            Technique::Fish { size, .. } => &self.houses[size..],
+            _ => &[],
+        }
+    }
+
+    /// Fins of a finned fish. Empty for other techniques.
+    pub fn fins(&self) -> &[usize] {
+        match &self.technique {
+            Technique::Fish { fins, .. } => fins,
+            _ => &[],
+        }
+    }
+
+    /// Human readable description, e.g. "hidden single in row 3: r3c5 = 7".
+    pub fn describe(&self, layout: &Layout) -> String {
+        let house_names = |houses: &[usize]| {
+            houses
+                .iter()
+                .map(|house_idx| layout.houses()[*house_idx].to_string())
+                .collect::<Vec<String>>()
+                .join(" and ")
+        };
+        let position_names = |positions: &[usize]| {
+            positions
+                .iter()
+                .map(|pos_idx| layout.position_name(*pos_idx))
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        let mut description = self.technique.to_string();
+        if let Technique::XyWing | Technique::XyzWing = self.technique {
+            description.push_str(&format!(
+                " with pivot {} and pincers {}",
+                position_names(&self.positions[..1]),
+                position_names(&self.positions[1..])
+            ));
+        } else if self.technique == Technique::WWing {
+            description.push_str(&format!(
+                " on {} with strong link in {} on {}",
+                position_names(&self.positions[..2]),
+                house_names(&self.houses),
+                position_names(&self.positions[2..])
+            ));
+        } else if let Technique::Fish { fins, .. } = &self.technique {
+            description.push_str(&format!(
+                " in {} covering {}",
+                house_names(self.base_sets()),
+                house_names(self.cover_sets())
+            ));
+            match fins.len() {
+                0 => {}
+                1 => description.push_str(&format!(" with fin {}", position_names(fins))),
+                _ => description.push_str(&format!(" with fins {}", position_names(fins))),
+            }
+        } else {
+            if !self.houses.is_empty() {
+                description.push_str(&format!(" in {}", house_names(&self.houses)));
+            }
+            if let Action::Eliminate(_) = self.action {
+                description.push_str(&format!(" on {}", position_names(&self.positions)));
+            }
+        }
+        let action = match &self.action {
+            Action::Place { pos_idx, digit } => format!(
+                "{} = {}",
+                layout.position_name(*pos_idx),
+                Layout::symbol(*digit)
+            ),
+            Action::Eliminate(eliminations) => eliminations
+                .iter()
+                .map(|elimination| {
+                    let digits: Vec<String> = elimination
+                        .candidates
+                        .iter()
+                        .map(|digit| Layout::symbol(digit).to_string())
+                        .collect();
+                    format!(
+                        "{} <> {}",
+                        layout.position_name(elimination.pos_idx),
+                        digits.join(",")
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+        };
+        format!("{}: {}", description, action)
+    }
+}
+
+/// Eliminations of `candidates` from the positions, skipping positions where none of them
+/// are left.
+pub(crate) fn eliminations_from(
+    problem: &SudokuCandidates,
+    positions: impl IntoIterator<Item = usize>,
+    candidates: Candidates,
+) -> Vec<Elimination> {
+    positions
+        .into_iter()
+        .filter_map(|pos_idx| {
+            let ruled_out = problem.get(pos_idx) & candidates;
+            if ruled_out.is_empty() {
+                None
+            } else {
+                Some(Elimination {
+                    pos_idx,
+                    candidates: ruled_out,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Finds the first application of a technique in the candidate grid.
+type Finder = fn(&SudokuCandidates) -> Option<Step>;
+
+/// Techniques working on the candidates alone, from the simplest to the most complex.
+/// Naked singles are found by the solver itself, as they depend on what was placed already.
+const TECHNIQUES: [Finder; 6] = [
+    find_hidden_single,
+    find_constraint_elimination,
+    find_locked_candidates,
+    find_subset,
+    find_fish,
+    find_wing,
+];
+
+pub(crate) fn find_hidden_single(problem: &SudokuCandidates) -> Option<Step> {
+    let layout = problem.layout();
+    for (house_idx, house) in layout.houses().iter().enumerate() {
+        for digit in Candidates::all(layout.size()).iter() {
+            let mut holders = house
+                .positions
+                .iter()
+                .filter(|pos_idx| problem.get(**pos_idx).contains(digit));
+            if let (Some(pos_idx), None) = (holders.next(), holders.next()) {
+                if problem.get(*pos_idx).len() > 1 {
+                    return Some(Step {
+                        technique: Technique::HiddenSingle,
+                        positions: vec![*pos_idx],
+                        houses: vec![house_idx],
+                        action: Action::Place {
+                            pos_idx: *pos_idx,
+                            digit,
+                        },
+                    });
+                }
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn find_constraint_elimination(problem: &SudokuCandidates) -> Option<Step> {
+    problem
+        .layout()
+        .constraints()
+        .iter()
+        .find_map(|constraint| {
+            let eliminations: Vec<Elimination> = constraint
+                .prune(problem)
+                .into_iter()
+                .flat_map(|elimination| {
+                    eliminations_from(problem, [elimination.pos_idx], elimination.candidates)
+                })
+                .collect();
+            if eliminations.is_empty() {
+                None
+            } else {
+                Some(Step {
+                    technique: Technique::Constraint,
+                    positions: constraint.positions().to_vec(),
+                    houses: vec![],
+                    action: Action::Eliminate(eliminations),
+                })
+            }
+        })
+}
+
+/// Candidate grid of the logical solver. Unlike the other solvers, placing a digit only
+/// removes it from the peers of the position, so every following single is a step of its own.
+struct LogicState {
+    problem: SudokuCandidates,
+    placed: Vec<bool>,
+    /// Solution the guesses are taken from, None if the problem is unsolvable.
+    solution: Option<SudokuCandidates>,
+}
+
+impl LogicState {
+    /// Starts from the candidates the problem was created from, with the givens placed.
+    /// The problem is solved once up front for the guesses.
+    fn new(problem: &SudokuCandidates) -> LogicState {
+        let mut state = LogicState {
+            problem: SudokuCandidates {
+                layout: Arc::clone(&problem.layout),
+                grid: problem.initial.to_vec(),
+                initial: Arc::clone(&problem.initial),
+            },
+            placed: vec![false; problem.grid.len()],
+            solution: BacktrackingSolver.solve(problem),
+        };
+        for pos_idx in 0..state.placed.len() {
+            if let (1, Some(digit)) = (
+                problem.initial[pos_idx].len(),
+                problem.initial[pos_idx].first(),
+            ) {
+                state.place(pos_idx, digit);
+            }
+        }
+        state
+    }
+
+    fn place(&mut self, pos_idx: usize, digit: u8) {
+        let layout = Arc::clone(&self.problem.layout);
+        self.problem.set(pos_idx, Candidates::single(digit));
+        self.placed[pos_idx] = true;
+        for peer_idx in layout.peers(pos_idx) {
+            let cands = self
+                .problem
+                .get(*peer_idx)
+                .without(Candidates::single(digit));
+            self.problem.set(*peer_idx, cands);
+        }
+    }
+
+    fn apply(&mut self, action: &Action) {
+        match action {
+            Action::Place { pos_idx, digit } => self.place(*pos_idx, *digit),
+            Action::Eliminate(eliminations) => {
+                for elimination in eliminations {
+                    let cands = self.problem.get(elimination.pos_idx);
+                    self.problem
+                        .set(elimination.pos_idx, cands.without(elimination.candidates));
+                }
+            }
+        }
+    }
+
+    fn is_solved(&self) -> bool {
+        self.placed.iter().all(|placed| *placed)
+    }
+
+    fn has_contradiction(&self) -> bool {
+        self.problem.grid.iter().any(|cands| cands.is_empty())
+            || self.problem.has_unresolvable_conflicts()
+    }
+
+    fn find_naked_single(&self) -> Option<Step> {
+        (0..self.placed.len())
+            .find(|pos_idx| !self.placed[*pos_idx] && self.problem.get(*pos_idx).len() == 1)
+            .map(|pos_idx| Step {
+                technique: Technique::NakedSingle,
+                positions: vec![pos_idx],
+                houses: vec![],
+                action: Action::Place {
+                    pos_idx,
+                    digit: self.problem.get(pos_idx).first().unwrap(),
+                },
+            })
+    }
+
+    /// Places the digit of the known solution at the position with the fewest candidates.
+    /// Returns None if the problem has no solution.
+    fn guess(&self) -> Option<Step> {
+        let solution = self.solution.as_ref()?;
+        let pos_idx = (0..self.placed.len())
+            .filter(|pos_idx| !self.placed[*pos_idx])
+            .min_by_key(|pos_idx| self.problem.get(*pos_idx).len())?;
+        Some(Step {
+            technique: Technique::Guess,
+            positions: vec![pos_idx],
+            houses: vec![],
+            action: Action::Place {
+                pos_idx,
+                digit: solution.get(pos_idx).first()?,
+            },
+        })
+    }
+}
+
+/// Solves like a human would, see `solve_steps`. Counting solutions falls back to the
+/// backtracking solver.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogicalSolver;
+
+impl LogicalSolver {
+    /// Solves the problem one technique at a time and returns the steps taken in order.
+    /// The steps start from the digits the problem was created with, the propagation done
+    /// while parsing is repeated as steps. The solution is None if the problem is unsolvable,
+    /// the steps up to the contradiction are returned anyway.
+    pub fn solve_steps(&self, problem: &SudokuCandidates) -> (Option<SudokuCandidates>, Vec<Step>) {
+        let mut state = LogicState::new(problem);
+        let mut steps = vec![];
+        loop {
+            if state.has_contradiction() {
+                return (None, steps);
+            }
+            if state.is_solved() {
+                return (Some(state.problem), steps);
+            }
+            let step = state.find_naked_single().or_else(|| {
+                TECHNIQUES
+                    .iter()
+                    .find_map(|technique| technique(&state.problem))
+            });
+            let step = match step.or_else(|| state.guess()) {
+                Some(step) => step,
+                None => return (None, steps),
+            };
+            state.apply(&step.action);
+            steps.push(step);
+        }
+    }
+}
+
+impl Solver for LogicalSolver {
+    fn count_solutions(&self, problem: &SudokuCandidates, limit: usize) -> SolutionCount {
+        BacktrackingSolver.count_solutions(problem, limit)
+    }
+
+    /// Every placement counts as a node, guesses are never wrong so there are no dead ends.
+    fn solve_with_stats(
+        &self,
+        problem: &SudokuCandidates,
+    ) -> (Option<SudokuCandidates>, SolveStats) {
+        let (solution, steps) = self.solve_steps(problem);
+        let nodes = steps
+            .iter()
+            .filter(|step| matches!(step.action, Action::Place { .. }))
+            .count();
+        (
+            solution,
+            SolveStats {
+                nodes,
+                dead_ends: 0,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps_of(problem_str: &str) -> (Option<SudokuCandidates>, Vec<Step>) {
+        LogicalSolver.solve_steps(&crate::parse_sudoku(problem_str).unwrap())
+    }
+
+    #[test]
+    fn test_singles_only() {
+        let (solution, steps) = steps_of(
+            "006037508700010900130050020002908000050020430600000090200005704003100060498600000",
+        );
+        let solution = solution.unwrap();
+        assert!(solution.is_correct());
+        // one placement for every empty position, no guesses
+        assert_eq!(steps.len(), 49);
+        for step in &steps {
+            assert_ne!(step.technique, Technique::Guess);
+            match step.action {
+                Action::Place { pos_idx, digit } => {
+                    assert_eq!(solution.get(pos_idx), Candidates::single(digit))
+                }
+                Action::Eliminate(_) => panic!("unexpected elimination"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_guess_is_marked() {
+        let (solution, steps) = steps_of(
+            "800000000003600000070090200050007000000045700000100030001000068008500010090000400",
+        );
+        assert_eq!(
+            solution.unwrap().to_continuous_string(),
+            "812753649943682175675491283154237896369845721287169534521974368438526917796318452"
+        );
+        // no single at the start, after a few guesses logic takes over
+        assert_eq!(steps[0].technique, Technique::Guess);
+        assert_eq!(
+            steps[0].describe(&Layout::new(3, 3)).split(':').next(),
+            Some("guess")
+        );
+        assert!(steps.last().unwrap().technique != Technique::Guess);
+    }
+
+    #[test]
+    fn test_elimination_steps() {
+        let problem = crate::parse_sudoku(
+            "002000600040080070005001280100007800069005720007000034000900000000006000000010400",
+        )
+        .unwrap();
+        let (solution, steps) = LogicalSolver.solve_steps(&problem);
+        assert!(solution.unwrap().is_correct());
+        assert!(steps.iter().all(|step| step.technique != Technique::Guess));
+        let techniques: Vec<String> = steps
+            .iter()
+            .filter(|step| matches!(step.action, Action::Eliminate(_)))
+            .map(|step| step.technique.to_string())
+            .collect();
+        assert_eq!(
+            techniques,
+            vec![
+                "pointing",
+                "pointing",
+                "pointing",
+                "pointing",
+                "claiming",
+                "naked pair"
+            ]
+        );
+        let pair = steps
+            .iter()
+            .find(|step| step.technique == Technique::NakedSubset(2))
+            .unwrap();
+        assert_eq!(
+            pair.describe(problem.layout()),
+            "naked pair in column 3 on r2c3 r9c3: r4c3 <> 3, r7c3 <> 3,6, r8c3 <> 3"
+        );
+    }
+
+    #[test]
+    fn test_fish_steps() {
+        let problem = crate::parse_sudoku(
+            "810000640003080000005090200004230000300005701000009500000000060000020000096000452",
+        )
+        .unwrap();
+        let (solution, steps) = LogicalSolver.solve_steps(&problem);
+        assert!(solution.unwrap().is_correct());
+        assert!(steps.iter().all(|step| step.technique != Technique::Guess));
+        let fish: Vec<String> = steps
+            .iter()
+            .filter(|step| !step.fins().is_empty())
+            .map(|step| step.describe(problem.layout()))
+            .collect();
+        assert_eq!(
+            fish,
+            vec![
+                "sashimi X-Wing in row 4 and row 9 covering column 1 and column 5 with fin r4c6: \
+                 r6c5 <> 1",
+                "finned Swordfish in row 1 and row 6 and row 9 covering column 1 and column 4 \
+                 and column 5 with fin r6c3: r4c1 <> 7",
+                "sashimi Swordfish in row 1 and row 6 and row 9 covering column 3 and column 4 \
+                 and column 5 with fin r9c1: r7c3 <> 7, r8c3 <> 7"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wing_steps() {
+        let problem = crate::parse_sudoku(
+            "010000009003602000670090000050007890000800000200160500000000008038500010090310002",
+        )
+        .unwrap();
+        let (solution, steps) = LogicalSolver.solve_steps(&problem);
+        assert!(solution.unwrap().is_correct());
+        assert!(steps.iter().all(|step| step.technique != Technique::Guess));
+        let wings: Vec<String> = steps
+            .iter()
+            .filter(|step| {
+                matches!(
+                    step.technique,
+                    Technique::XyWing | Technique::XyzWing | Technique::WWing
+                )
+            })
+            .map(|step| step.describe(problem.layout()))
+            .collect();
+        assert_eq!(
+            wings,
+            vec![
+                "XYZ-Wing with pivot r9c7 and pincers r9c3 r8c9: r9c8 <> 4",
+                "XY-Wing with pivot r5c7 and pincers r5c1 r9c7: r9c1 <> 4",
+                "W-Wing on r1c8 r9c3 with strong link in column 7 on r1c7 r9c7: r9c8 <> 6"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hidden_single_step() {
+        // 1 is ruled out of the first row except for r1c1 by the 1s in columns 2 and 3 and in
+        // rows 2 and 3 (via the cells)
+        let problem = crate::parse_sudoku(
+            "000000000000100000000000100010000000000010000000000010001000000000001000000000000",
+        )
+        .unwrap();
+        // parsing places hidden singles already, the logical solver starts from the givens
+        assert_eq!(problem.get(0), Candidates::single(1));
+        let state = LogicState::new(&problem);
+        let step = find_hidden_single(&state.problem).unwrap();
+        assert_eq!(step.technique, Technique::HiddenSingle);
+        assert_eq!(
+            step.action,
+            Action::Place {
+                pos_idx: 0,
+                digit: 1
+            }
+        );
+        assert_eq!(
+            step.describe(problem.layout()),
+            "hidden single in row 1: r1c1 = 1"
+        );
+    }
+
+    #[test]
+    fn test_constraint_step() {
+        let problem_str = "0".repeat(81) + "\ncage: 3 r1c1 r1c2";
+        let (_, steps) = steps_of(&problem_str);
+        assert_eq!(steps[0].technique, Technique::Constraint);
+        let layout = crate::parse_sudoku(&problem_str).unwrap().layout().clone();
+        assert_eq!(
+            steps[0].describe(&layout),
+            "constraint on r1c1 r1c2: r1c1 <> 3,4,5,6,7,8,9, r1c2 <> 3,4,5,6,7,8,9"
+        );
+    }
+}
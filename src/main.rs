@@ -9,10 +9,14 @@ use chrono::Local;
 use clap::{Arg, ArgAction, Command};
 use env_logger::Builder;
 use log::LevelFilter;
+use std::fs;
 use std::io::Write;
 use std::time::Instant;
 
-use sudoku_solver::{parse_sudokus, solve_sudoku};
+use sudoku_solver::{
+    parse_sudokus_with_variant, split_problems, try_parse_sudoku_with_variant, LogicalSolver,
+    Solver, SolverKind, Variant,
+};
 
 fn main() {
     let matches = Command::new("Sudoku Solver")
@@ -25,6 +29,42 @@ fn main() {
                 .required(true)
                 .index(1),
         )
+        .arg(
+            Arg::new("solver")
+                .long("solver")
+                .value_parser(SolverKind::NAMES)
+                .default_value("backtracking")
+                .help("Algorithm used to solve the problems"),
+        )
+        .arg(
+            Arg::new("variant")
+                .long("variant")
+                .value_parser(|names: &str| names.parse::<Variant>())
+                .default_value("")
+                .help(format!(
+                    "Comma separated list of additional rules applied to all problems, possible values: {}",
+                    Variant::NAMES.join(", ")
+                )),
+        )
+        .arg(
+            Arg::new("count")
+                .long("count")
+                .action(ArgAction::SetTrue)
+                .help("Instead of solving, report for each line whether the problem has a unique solution"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(2..))
+                .default_value("2")
+                .help("Stop counting solutions once this many were found, at least 2"),
+        )
+        .arg(
+            Arg::new("steps")
+                .long("steps")
+                .action(ArgAction::SetTrue)
+                .help("Solve with the logical solver and print each step taken"),
+        )
         .arg(
             Arg::new("v")
                 .short('v')
@@ -52,8 +92,21 @@ fn main() {
         .init();
 
     let prob = matches.get_one::<String>("INPUT").unwrap();
+    let solver = matches
+        .get_one::<String>("solver")
+        .unwrap()
+        .parse::<SolverKind>()
+        .unwrap()
+        .solver();
+    let variant = *matches.get_one::<Variant>("variant").unwrap();
+
+    if matches.get_flag("count") {
+        let limit = *matches.get_one::<usize>("limit").unwrap();
+        count_solutions(prob, variant, solver.as_ref(), limit);
+        return;
+    }
 
-    let sudoku_problems = parse_sudokus(prob);
+    let sudoku_problems = parse_sudokus_with_variant(prob, variant);
 
     let num_sudokus = sudoku_problems.len();
     let mut num_unsolvable_sudokus = 0;
@@ -61,7 +114,15 @@ fn main() {
 
     for prob in sudoku_problems {
         info!("Starting with problem: {}", prob);
-        let solution = solve_sudoku(Some(prob), 0);
+        let solution = if matches.get_flag("steps") {
+            let (solution, steps) = LogicalSolver.solve_steps(&prob);
+            for step in steps {
+                info!("{}", step.describe(prob.layout()));
+            }
+            solution
+        } else {
+            solver.solve(&prob)
+        };
 
         if let Some(solved) = solution {
             info!("Problem solved:{}", solved);
@@ -87,3 +148,42 @@ fn main() {
         );
     }
 }
+
+fn count_solutions(filepath: &str, variant: Variant, solver: &dyn Solver, limit: usize) {
+    let contents = fs::read_to_string(filepath).expect("Something went wrong reading the file");
+    let mut num_unique_sudokus = 0;
+    let mut num_sudokus = 0;
+    let mut num_invalid_lines = 0;
+
+    for (line_no, problem_str) in split_problems(&contents) {
+        let count = match try_parse_sudoku_with_variant(&problem_str, variant) {
+            Ok(Some(prob)) => solver.count_solutions(&prob, limit),
+            Ok(None) => {
+                warn!("Line {}: problem contains conflicts!", line_no);
+                num_sudokus += 1;
+                continue;
+            }
+            Err(err) => {
+                warn!("Line {}: failed to parse problem - {}", line_no, err);
+                num_invalid_lines += 1;
+                continue;
+            }
+        };
+        if count.is_unique() {
+            num_unique_sudokus += 1;
+        }
+        num_sudokus += 1;
+        info!("Line {}: {}", line_no, count);
+    }
+
+    info!(
+        "{} out of {} sudokus have a unique solution.",
+        num_unique_sudokus, num_sudokus
+    );
+    if num_invalid_lines > 0 {
+        warn!(
+            "Skipped {} problems that could not be parsed.",
+            num_invalid_lines
+        );
+    }
+}
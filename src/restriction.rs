@@ -0,0 +1,101 @@
+//! Markers restricting the digits of single positions, e.g. the gray squares of an even/odd
+//! sudoku that have to hold an even digit.
+
+use crate::constraint::{Constraint, Elimination};
+use crate::{Candidates, SudokuCandidates};
+
+/// Digits of a predefined set for a grid of the given size. Low and high are the lower and
+/// upper half of the digits without the middle one, e.g. 1-4 and 6-9 for 9x9.
+pub(crate) fn named_digits(name: &str, size: usize) -> Option<Candidates> {
+    let all = Candidates::all(size);
+    let half = (size / 2) as u8;
+    let digits = match name {
+        "even" => all.iter().filter(|digit| digit % 2 == 0).collect(),
+        "odd" => all.iter().filter(|digit| digit % 2 == 1).collect(),
+        "low" => Candidates::range(1, half),
+        "high" => Candidates::range(size as u8 + 1 - half, size as u8),
+        _ => return None,
+    };
+    Some(digits)
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct DigitRestriction {
+    positions: Vec<usize>,
+    allowed: Candidates,
+}
+
+impl DigitRestriction {
+    pub fn new(positions: Vec<usize>, allowed: Candidates) -> Result<DigitRestriction, String> {
+        if allowed.is_empty() {
+            return Err("No digits allowed for the restricted positions".to_owned());
+        }
+        Ok(DigitRestriction { positions, allowed })
+    }
+}
+
+impl Constraint for DigitRestriction {
+    fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+        self.positions.iter().any(|pos_idx| {
+            let cands = problem.get(*pos_idx);
+            cands.len() == 1 && (cands & self.allowed).is_empty()
+        })
+    }
+
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+        self.positions
+            .iter()
+            .filter_map(|pos_idx| {
+                let ruled_out = problem.get(*pos_idx).without(self.allowed);
+                if ruled_out.is_empty() {
+                    None
+                } else {
+                    Some(Elimination {
+                        pos_idx: *pos_idx,
+                        candidates: ruled_out,
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_digits() {
+        let digits = |name: &str, size: usize| -> Vec<u8> {
+            named_digits(name, size).unwrap().iter().collect()
+        };
+        assert_eq!(digits("even", 9), vec![2, 4, 6, 8]);
+        assert_eq!(digits("odd", 9), vec![1, 3, 5, 7, 9]);
+        assert_eq!(digits("low", 9), vec![1, 2, 3, 4]);
+        assert_eq!(digits("high", 9), vec![6, 7, 8, 9]);
+        assert_eq!(digits("low", 6), vec![1, 2, 3]);
+        assert_eq!(digits("high", 6), vec![4, 5, 6]);
+        assert!(named_digits("middle", 9).is_none());
+    }
+
+    #[test]
+    fn test_prune_restriction() {
+        let problem = crate::parse_sudoku(&("2".to_owned() + &"0".repeat(80))).unwrap();
+        let even = DigitRestriction::new(vec![0, 1], named_digits("even", 9).unwrap()).unwrap();
+        assert!(!even.has_conflict(&problem));
+        assert_eq!(
+            even.prune(&problem),
+            vec![Elimination {
+                pos_idx: 1,
+                candidates: named_digits("odd", 9).unwrap(),
+            }]
+        );
+        let odd = DigitRestriction::new(vec![0], named_digits("odd", 9).unwrap()).unwrap();
+        assert!(odd.has_conflict(&problem));
+        assert!(DigitRestriction::new(vec![0], Candidates::NONE).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Sandwich sudoku: clues outside the grid give the sum of the digits between the smallest
+//! and the largest digit (1 and 9 for 9x9) of a row or column.
+
+use crate::constraint::{Constraint, Elimination};
+use crate::killer::find_combinations;
+use crate::{Candidates, SudokuCandidates};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Sandwich {
+    // positions of the row or column in order
+    positions: Vec<usize>,
+    sum: u32,
+    // for each number of positions between the crusts, the sets of digits adding up to the sum
+    fillings: Vec<Vec<Candidates>>,
+}
+
+impl Sandwich {
+    pub fn new(positions: Vec<usize>, sum: u32) -> Result<Sandwich, String> {
+        let size = positions.len();
+        let fillings: Vec<Vec<Candidates>> = (0..size - 1)
+            .map(|count| {
+                let mut combinations = vec![];
+                find_combinations(
+                    2,
+                    size as u8 - 1,
+                    count,
+                    sum,
+                    Candidates::NONE,
+                    &mut combinations,
+                );
+                combinations
+            })
+            .collect();
+        if fillings.iter().all(|combinations| combinations.is_empty()) {
+            return Err(format!(
+                "No digits between 2 and {} add up to {}",
+                size - 1,
+                sum
+            ));
+        }
+        Ok(Sandwich {
+            positions,
+            sum,
+            fillings,
+        })
+    }
+
+    /// Smallest and largest digit, i.e. the crusts of the sandwich.
+    fn crusts(&self) -> (Candidates, Candidates) {
+        (
+            Candidates::single(1),
+            Candidates::single(self.positions.len() as u8),
+        )
+    }
+}
+
+impl Constraint for Sandwich {
+    fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+        let (low, high) = self.crusts();
+        let fixed = |idx: usize| {
+            let cands = problem.get(self.positions[idx]);
+            if cands.len() == 1 {
+                cands.first()
+            } else {
+                None
+            }
+        };
+        let low_idx = (0..self.positions.len()).find(|idx| fixed(*idx) == low.first());
+        let high_idx = (0..self.positions.len()).find(|idx| fixed(*idx) == high.first());
+        let (first, last) = match (low_idx, high_idx) {
+            (Some(low_idx), Some(high_idx)) => (low_idx.min(high_idx), low_idx.max(high_idx)),
+            _ => return false,
+        };
+        let inner: Vec<Option<u8>> = (first + 1..last).map(fixed).collect();
+        let fixed_sum: u32 = inner.iter().flatten().map(|digit| *digit as u32).sum();
+        fixed_sum > self.sum || (inner.iter().all(|digit| digit.is_some()) && fixed_sum != self.sum)
+    }
+
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+        let cands: Vec<Candidates> = self
+            .positions
+            .iter()
+            .map(|pos_idx| problem.get(*pos_idx))
+            .collect();
+        let (low, high) = self.crusts();
+        let crusts = low | high;
+
+        // a digit stays possible if it is part of a placement of the crusts and a filling
+        // that fits the candidates
+        let mut allowed = vec![Candidates::NONE; cands.len()];
+        for (first, last) in (0..cands.len())
+            .flat_map(|first| (first + 1..cands.len()).map(move |last| (first, last)))
+        {
+            for (first_crust, last_crust) in [(low, high), (high, low)] {
+                if (cands[first] & first_crust).is_empty() || (cands[last] & last_crust).is_empty()
+                {
+                    continue;
+                }
+                for filling in &self.fillings[last - first - 1] {
+                    let outside = Candidates::all(cands.len()).without(*filling | crusts);
+                    let restricted: Vec<Candidates> = cands
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, cands)| match idx {
+                            idx if idx == first => first_crust,
+                            idx if idx == last => last_crust,
+                            idx if idx > first && idx < last => *cands & *filling,
+                            _ => *cands & outside,
+                        })
+                        .collect();
+                    if restricted.iter().any(|cands| cands.is_empty()) {
+                        continue;
+                    }
+                    for (allowed, restricted) in allowed.iter_mut().zip(&restricted) {
+                        *allowed = *allowed | *restricted;
+                    }
+                }
+            }
+        }
+
+        self.positions
+            .iter()
+            .zip(cands.iter().zip(&allowed))
+            .filter(|(_, (cands, allowed))| **cands != **allowed)
+            .map(|(pos_idx, (cands, allowed))| Elimination {
+                pos_idx: *pos_idx,
+                candidates: cands.without(*allowed),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fillings() {
+        let sandwich = Sandwich::new((0..9).collect(), 0).unwrap();
+        // the crusts are next to each other
+        assert_eq!(sandwich.fillings[0], vec![Candidates::NONE]);
+        assert!(sandwich.fillings[1..]
+            .iter()
+            .all(|fillings| fillings.is_empty()));
+        // 2 + ... + 8 = 35
+        assert!(Sandwich::new((0..9).collect(), 35).is_ok());
+        assert!(Sandwich::new((0..9).collect(), 36).is_err());
+        assert!(Sandwich::new((0..9).collect(), 1).is_err());
+    }
+
+    #[test]
+    fn test_prune_crusts() {
+        let problem = crate::parse_sudoku(&"0".repeat(81)).unwrap();
+        // all digits between 1 and 9, they have to be at the ends of the row
+        let sandwich = Sandwich::new((0..9).collect(), 35).unwrap();
+        let eliminations = sandwich.prune(&problem);
+        assert_eq!(eliminations.len(), 9);
+        let ends = Candidates::single(1) | Candidates::single(9);
+        for elimination in eliminations {
+            let remaining = problem
+                .get(elimination.pos_idx)
+                .without(elimination.candidates);
+            match elimination.pos_idx {
+                0 | 8 => assert_eq!(remaining, ends),
+                _ => assert_eq!(remaining, Candidates::range(2, 8)),
+            }
+        }
+    }
+}
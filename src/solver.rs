@@ -0,0 +1,217 @@
+//! Common interface of the available solving algorithms.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::dlx::DlxSolver;
+use crate::logic::LogicalSolver;
+use crate::{all_solutions, BacktrackingSearch, SudokuCandidates};
+
+/// Statistics collected while solving a problem.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SolveStats {
+    /// Number of placements tried during the search.
+    pub nodes: usize,
+    /// Number of times the search ran into a contradiction and had to backtrack.
+    pub dead_ends: usize,
+}
+
+/// Result of counting the solutions of a problem.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolutionCount {
+    /// The search space was exhausted, the problem has exactly this many solutions.
+    Exactly(usize),
+    /// The search stopped at the limit, there may be more solutions.
+    AtLeast(usize),
+}
+
+impl SolutionCount {
+    pub(crate) fn from_limited(num_solutions: usize, limit: usize) -> SolutionCount {
+        if num_solutions < limit {
+            SolutionCount::Exactly(num_solutions)
+        } else {
+            SolutionCount::AtLeast(num_solutions)
+        }
+    }
+
+    /// True if the problem is well formed, i.e. it has exactly one solution.
+    pub fn is_unique(self) -> bool {
+        self == SolutionCount::Exactly(1)
+    }
+
+    pub fn is_solvable(self) -> bool {
+        self != SolutionCount::Exactly(0) && self != SolutionCount::AtLeast(0)
+    }
+}
+
+impl fmt::Display for SolutionCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolutionCount::Exactly(0) => write!(f, "no solution"),
+            SolutionCount::Exactly(1) => write!(f, "unique solution"),
+            SolutionCount::Exactly(n) => write!(f, "{} solutions", n),
+            SolutionCount::AtLeast(n) => write!(f, "at least {} solutions", n),
+        }
+    }
+}
+
+/// A sudoku solving algorithm.
+pub trait Solver {
+    /// Returns the first solution found, None if the problem is unsolvable.
+    fn solve(&self, problem: &SudokuCandidates) -> Option<SudokuCandidates> {
+        self.solve_with_stats(problem).0
+    }
+
+    /// Counts the solutions of the problem, stops searching once `limit` solutions were found.
+    fn count_solutions(&self, problem: &SudokuCandidates, limit: usize) -> SolutionCount;
+
+    /// Like `solve`, but also reports how much searching was necessary.
+    fn solve_with_stats(
+        &self,
+        problem: &SudokuCandidates,
+    ) -> (Option<SudokuCandidates>, SolveStats);
+}
+
+/// Constraint propagation combined with backtracking, see `solve_sudoku`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BacktrackingSolver;
+
+impl Solver for BacktrackingSolver {
+    fn count_solutions(&self, problem: &SudokuCandidates, limit: usize) -> SolutionCount {
+        let num_solutions = all_solutions(Some(problem.clone())).take(limit).count();
+        SolutionCount::from_limited(num_solutions, limit)
+    }
+
+    fn solve_with_stats(
+        &self,
+        problem: &SudokuCandidates,
+    ) -> (Option<SudokuCandidates>, SolveStats) {
+        let mut search = BacktrackingSearch::new(problem.clone());
+        let solution = search.next_solution();
+        (solution, search.stats)
+    }
+}
+
+/// Selects one of the built in solvers, e.g. from a command line option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolverKind {
+    Backtracking,
+    Dlx,
+    Logical,
+}
+
+impl SolverKind {
+    pub const NAMES: [&'static str; 3] = ["backtracking", "dlx", "logical"];
+
+    pub fn solver(self) -> Box<dyn Solver> {
+        match self {
+            SolverKind::Backtracking => Box::new(BacktrackingSolver),
+            SolverKind::Dlx => Box::new(DlxSolver),
+            SolverKind::Logical => Box::new(LogicalSolver),
+        }
+    }
+}
+
+impl FromStr for SolverKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<SolverKind, String> {
+        match name {
+            "backtracking" => Ok(SolverKind::Backtracking),
+            "dlx" => Ok(SolverKind::Dlx),
+            "logical" => Ok(SolverKind::Logical),
+            _ => Err(format!(
+                "Unknown solver {}, expected one of {:?}",
+                name,
+                SolverKind::NAMES
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SolverKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SolverKind::Backtracking => SolverKind::NAMES[0],
+            SolverKind::Dlx => SolverKind::NAMES[1],
+            SolverKind::Logical => SolverKind::NAMES[2],
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert_problem_str;
+
+    fn problem(problem_str: &str) -> SudokuCandidates {
+        SudokuCandidates::from_vec(convert_problem_str(problem_str)).unwrap()
+    }
+
+    #[test]
+    fn test_solvers_agree() {
+        let prob = problem(
+            "100007860007008010800200009000000002400010000009005000608000000000050900000009304",
+        );
+        let solutions: Vec<String> = SolverKind::NAMES
+            .iter()
+            .map(|name| name.parse::<SolverKind>().unwrap().solver())
+            .map(|solver| solver.solve(&prob).unwrap().to_continuous_string())
+            .collect();
+        assert!(solutions.iter().all(|solution| *solution == solutions[0]));
+    }
+
+    #[test]
+    fn test_count_solutions_respects_limit() {
+        let empty = problem(&"0".repeat(81));
+        for kind in &[SolverKind::Backtracking, SolverKind::Dlx] {
+            assert_eq!(
+                kind.solver().count_solutions(&empty, 3),
+                SolutionCount::AtLeast(3)
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_solutions_uniqueness() {
+        let unique = problem(
+            "800000000003600000070090200050007000000045700000100030001000068008500010090000400",
+        );
+        // same problem with the 8 in the first row removed
+        let ambiguous = problem(
+            "000000000003600000070090200050007000000045700000100030001000068008500010090000400",
+        );
+        for kind in &[SolverKind::Backtracking, SolverKind::Dlx] {
+            let count = kind.solver().count_solutions(&unique, 10);
+            assert_eq!(count, SolutionCount::Exactly(1));
+            assert!(count.is_unique());
+            let count = kind.solver().count_solutions(&ambiguous, 2);
+            assert!(!count.is_unique());
+            assert!(count.is_solvable());
+        }
+    }
+
+    #[test]
+    fn test_solve_with_stats() {
+        let prob = problem(
+            "800000000003600000070090200050007000000045700000100030001000068008500010090000400",
+        );
+        for kind in &[SolverKind::Backtracking, SolverKind::Dlx] {
+            let (solution, stats) = kind.solver().solve_with_stats(&prob);
+            assert!(solution.unwrap().is_correct());
+            assert!(stats.nodes > 0);
+            assert!(stats.dead_ends > 0);
+        }
+        let (solution, stats) = SolverKind::Logical.solver().solve_with_stats(&prob);
+        assert!(solution.unwrap().is_correct());
+        assert_eq!(stats.dead_ends, 0);
+    }
+
+    #[test]
+    fn test_unknown_solver_name() {
+        assert!("foo".parse::<SolverKind>().is_err());
+        assert_eq!("dlx".parse::<SolverKind>(), Ok(SolverKind::Dlx));
+        assert_eq!("logical".parse::<SolverKind>(), Ok(SolverKind::Logical));
+    }
+}
@@ -0,0 +1,458 @@
+//! Naked and hidden subsets (pairs, triples and quads) within a house.
+//! N positions of a house whose candidates together are N digits hold exactly these digits,
+//! so the digits are removed from the other positions of the house (naked subset).
+//! N digits that together fit into N positions of a house only are placed there, so all other
+//! candidates of these positions are removed (hidden subset).
+
+use crate::constraint::Elimination;
+use crate::layout::House;
+use crate::logic::{eliminations_from, Action, Step, Technique};
+use crate::{Candidates, SudokuCandidates, TrailEntry, MAX_SIZE};
+use std::sync::Arc;
+
+/// Largest subset searched for, which bounds the number of combinations tried in the houses
+/// of large grids. Nothing is missed in 9x9 grids: the open positions of a house not in a
+/// subset of five or more form a hidden or naked subset of at most four.
+pub(crate) const MAX_SUBSET_SIZE: usize = 4;
+
+/// Calls `found` for combinations of `size` indices out of `0..len` until it returns true.
+/// Combinations are built up index by index, `extend` derives the state of the longer
+/// combination from the state so far and the added index. If it returns None, the combination
+/// is abandoned together with everything built on it.
+/// Returns whether `found` returned true.
+pub(crate) fn combinations<T: Copy>(
+    len: usize,
+    size: usize,
+    initial: T,
+    mut extend: impl FnMut(T, usize) -> Option<T>,
+    mut found: impl FnMut(&[usize], T) -> bool,
+) -> bool {
+    fn walk<T: Copy>(
+        len: usize,
+        size: usize,
+        chosen: &mut [usize; MAX_SIZE],
+        depth: usize,
+        state: T,
+        extend: &mut impl FnMut(T, usize) -> Option<T>,
+        found: &mut impl FnMut(&[usize], T) -> bool,
+    ) -> bool {
+        if depth == size {
+            return found(&chosen[..depth], state);
+        }
+        let first = if depth == 0 { 0 } else { chosen[depth - 1] + 1 };
+        for idx in first..len {
+            if let Some(extended) = extend(state, idx) {
+                chosen[depth] = idx;
+                if walk(len, size, chosen, depth + 1, extended, extend, found) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+    walk(
+        len,
+        size,
+        &mut [0; MAX_SIZE],
+        0,
+        initial,
+        &mut extend,
+        &mut found,
+    )
+}
+
+/// Calls `found` for combinations of `size` masks whose union has exactly `size` bits, until
+/// it returns true. Combinations are abandoned as soon as their union has too many bits,
+/// which keeps the search cheap for large grids.
+/// Returns whether `found` returned true.
+pub(crate) fn locked_sets(
+    masks: &[u32],
+    size: usize,
+    found: &mut dyn FnMut(&[usize], u32) -> bool,
+) -> bool {
+    combinations(
+        masks.len(),
+        size,
+        0,
+        |union, idx| {
+            let extended = union | masks[idx];
+            if masks[idx] == 0 || extended.count_ones() as usize > size {
+                None
+            } else {
+                Some(extended)
+            }
+        },
+        |chosen, union| union.count_ones() as usize == size && found(chosen, union),
+    )
+}
+
+/// First naked subset of the given size in the house that removes any candidates.
+fn naked_subset(problem: &SudokuCandidates, house: &House, size: usize) -> Option<Step> {
+    // solved positions take part as singles already
+    let open: Vec<usize> = house
+        .positions
+        .iter()
+        .copied()
+        .filter(|pos_idx| problem.get(*pos_idx).len() > 1)
+        .collect();
+    let masks: Vec<u32> = open.iter().map(|pos_idx| problem.get(*pos_idx).0).collect();
+    let mut step = None;
+    locked_sets(&masks, size, &mut |chosen, digits| {
+        let positions: Vec<usize> = chosen.iter().map(|idx| open[*idx]).collect();
+        let others = open
+            .iter()
+            .copied()
+            .filter(|pos_idx| !positions.contains(pos_idx));
+        let eliminations = eliminations_from(problem, others, Candidates(digits));
+        if eliminations.is_empty() {
+            return false;
+        }
+        step = Some(Step {
+            technique: Technique::NakedSubset(size),
+            positions,
+            houses: vec![],
+            action: Action::Eliminate(eliminations),
+        });
+        true
+    });
+    step
+}
+
+/// First hidden subset of the given size in the house that removes any candidates.
+fn hidden_subset(problem: &SudokuCandidates, house: &House, size: usize) -> Option<Step> {
+    let all = Candidates::all(problem.layout().size());
+    let solved = house
+        .positions
+        .iter()
+        .map(|pos_idx| problem.get(*pos_idx))
+        .filter(|cands| cands.len() == 1)
+        .fold(Candidates::NONE, |solved, cands| solved | cands);
+    let open: Vec<u8> = all.without(solved).iter().collect();
+    // bit i is set if the digit fits into the i-th position of the house
+    let masks: Vec<u32> = open
+        .iter()
+        .map(|digit| {
+            house
+                .positions
+                .iter()
+                .enumerate()
+                .filter(|(_, pos_idx)| problem.get(**pos_idx).contains(*digit))
+                .fold(0, |mask, (idx, _)| mask | 1 << idx)
+        })
+        .collect();
+    let mut step = None;
+    locked_sets(&masks, size, &mut |chosen, house_indices| {
+        let digits: Candidates = chosen.iter().map(|idx| open[*idx]).collect();
+        let positions: Vec<usize> = house
+            .positions
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| house_indices & 1 << idx != 0)
+            .map(|(_, pos_idx)| *pos_idx)
+            .collect();
+        let eliminations =
+            eliminations_from(problem, positions.iter().copied(), all.without(digits));
+        if eliminations.is_empty() {
+            return false;
+        }
+        step = Some(Step {
+            technique: Technique::HiddenSubset(size),
+            positions,
+            houses: vec![],
+            action: Action::Eliminate(eliminations),
+        });
+        true
+    });
+    step
+}
+
+type SubsetFinder = fn(&SudokuCandidates, &House, usize) -> Option<Step>;
+type SubsetApplier = fn(&mut SudokuCandidates, &mut Vec<TrailEntry>, &House) -> Option<bool>;
+
+/// First naked or hidden subset in any house, smaller subsets first and naked before hidden
+/// ones of the same size.
+pub(crate) fn find_subset(problem: &SudokuCandidates) -> Option<Step> {
+    let houses = problem.layout().houses();
+    for size in 2..=MAX_SUBSET_SIZE {
+        for finder in &[naked_subset as SubsetFinder, hidden_subset] {
+            for (house_idx, house) in houses.iter().enumerate() {
+                if let Some(mut step) = finder(problem, house, size) {
+                    step.houses.push(house_idx);
+                    return Some(step);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Applies the eliminations one after the other, stopping at a contradiction.
+/// Returns the result of `apply_elimination` for all of them together.
+fn apply_all(
+    problem: &mut SudokuCandidates,
+    trail: &mut Vec<TrailEntry>,
+    eliminations: impl Iterator<Item = Elimination>,
+) -> Option<bool> {
+    let mut changed = false;
+    for elimination in eliminations {
+        changed |= problem.apply_elimination(&elimination, trail)?;
+    }
+    Some(changed)
+}
+
+/// Like `naked_subset` for all sizes, but applies the first subset that removes any candidates
+/// right away.
+fn apply_naked_subset(
+    problem: &mut SudokuCandidates,
+    trail: &mut Vec<TrailEntry>,
+    house: &House,
+) -> Option<bool> {
+    let mut open = [0; MAX_SIZE];
+    let mut masks = [0; MAX_SIZE];
+    let mut len = 0;
+    for pos_idx in &house.positions {
+        let cands = problem.get(*pos_idx);
+        if cands.len() > 1 {
+            open[len] = *pos_idx;
+            masks[len] = cands.0;
+            len += 1;
+        }
+    }
+    let mut applied = Some(false);
+    // a subset of all open positions has nothing left to remove
+    for size in 2..=MAX_SUBSET_SIZE.min(len.saturating_sub(1)) {
+        locked_sets(&masks[..len], size, &mut |chosen, digits| {
+            let others = (0..len)
+                .filter(|idx| !chosen.contains(idx) && masks[*idx] & digits != 0)
+                .map(|idx| Elimination {
+                    pos_idx: open[idx],
+                    candidates: Candidates(digits),
+                });
+            applied = apply_all(problem, trail, others);
+            applied != Some(false)
+        });
+        if applied != Some(false) {
+            break;
+        }
+    }
+    applied
+}
+
+/// Like `hidden_subset` for all sizes, but applies the first subset that removes any candidates
+/// right away.
+fn apply_hidden_subset(
+    problem: &mut SudokuCandidates,
+    trail: &mut Vec<TrailEntry>,
+    house: &House,
+) -> Option<bool> {
+    let all = Candidates::all(problem.layout().size());
+    let solved = house
+        .positions
+        .iter()
+        .map(|pos_idx| problem.get(*pos_idx))
+        .filter(|cands| cands.len() == 1)
+        .fold(Candidates::NONE, |solved, cands| solved | cands);
+    let mut open = [0; MAX_SIZE];
+    let mut masks = [0; MAX_SIZE];
+    let mut len = 0;
+    for digit in all.without(solved).iter() {
+        open[len] = digit;
+        masks[len] = house
+            .positions
+            .iter()
+            .enumerate()
+            .filter(|(_, pos_idx)| problem.get(**pos_idx).contains(digit))
+            .fold(0, |mask, (idx, _)| mask | 1 << idx);
+        len += 1;
+    }
+    let mut applied = Some(false);
+    // the other open digits of a larger hidden subset form a smaller naked subset, or a naked
+    // single that the peers took care of already
+    for size in 2..=MAX_SUBSET_SIZE.min(len / 2) {
+        locked_sets(&masks[..len], size, &mut |chosen, house_indices| {
+            let digits: Candidates = chosen.iter().map(|idx| open[*idx]).collect();
+            let positions = house
+                .positions
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| house_indices & 1 << idx != 0)
+                .map(|(_, pos_idx)| Elimination {
+                    pos_idx: *pos_idx,
+                    candidates: all.without(digits),
+                });
+            applied = apply_all(problem, trail, positions);
+            applied != Some(false)
+        });
+        if applied != Some(false) {
+            break;
+        }
+    }
+    applied
+}
+
+/// `find_subset` for the search: applies the first subset of each house that removes any
+/// candidates, without building steps. Trail and result as for `apply_elimination`.
+pub(crate) fn apply_subsets(
+    problem: &mut SudokuCandidates,
+    trail: &mut Vec<TrailEntry>,
+) -> Option<bool> {
+    let layout = Arc::clone(&problem.layout);
+    let mut changed = false;
+    for house in layout.houses() {
+        for apply in &[apply_naked_subset as SubsetApplier, apply_hidden_subset] {
+            changed |= apply(problem, trail, house)?;
+        }
+    }
+    Some(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty grid with the given candidates in the first row.
+    fn first_row(candidates: &[&[u8]]) -> SudokuCandidates {
+        let marks: Vec<(usize, &[u8])> = candidates.iter().copied().enumerate().collect();
+        SudokuCandidates::pencil_marks(&marks)
+    }
+
+    #[test]
+    fn test_combinations() {
+        let mut all = vec![];
+        combinations(
+            4,
+            2,
+            (),
+            |_, _| Some(()),
+            |chosen, _| {
+                all.push(chosen.to_vec());
+                false
+            },
+        );
+        assert_eq!(all.len(), 6);
+        assert_eq!(all[0], vec![0, 1]);
+        // the state is the sum of the indices so far, sums above 3 are abandoned
+        let mut sums = vec![];
+        combinations(
+            4,
+            2,
+            0,
+            |sum, idx| Some(sum + idx).filter(|sum| *sum <= 3),
+            |_, sum| {
+                sums.push(sum);
+                false
+            },
+        );
+        assert_eq!(sums, vec![1, 2, 3, 3]);
+        assert!(combinations(
+            4,
+            2,
+            (),
+            |_, _| Some(()),
+            |chosen, _| chosen[1] == 3
+        ));
+    }
+
+    #[test]
+    fn test_locked_sets() {
+        let mut sets = vec![];
+        locked_sets(&[0b011, 0b110, 0b1000, 0b101], 3, &mut |chosen, union| {
+            sets.push((chosen.to_vec(), union));
+            false
+        });
+        assert_eq!(sets, vec![(vec![0, 1, 3], 0b111)]);
+        assert!(locked_sets(&[0b11, 0b11], 2, &mut |_, _| true));
+        assert!(!locked_sets(&[0b11, 0], 2, &mut |_, _| true));
+    }
+
+    #[test]
+    fn test_naked_pair() {
+        let problem = first_row(&[&[1, 2], &[1, 2]]);
+        let step = find_subset(&problem).unwrap();
+        assert_eq!(
+            step.describe(problem.layout()),
+            "naked pair in row 1 on r1c1 r1c2: r1c3 <> 1,2, r1c4 <> 1,2, r1c5 <> 1,2, r1c6 <> 1,2, \
+             r1c7 <> 1,2, r1c8 <> 1,2, r1c9 <> 1,2"
+        );
+        assert_eq!(step.positions, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_apply_subsets() {
+        // a naked triple in r1c1 to r1c3, which lies in the first row and the first cell
+        let nine: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let mut problem = first_row(&[&[1, 2], &[2, 3], &[1, 3], nine]);
+        let mut trail = vec![];
+        assert_eq!(apply_subsets(&mut problem, &mut trail), Some(true));
+        assert_eq!(problem.get(3), nine[3..].iter().copied().collect());
+        assert_eq!(problem.get(9), nine[3..].iter().copied().collect());
+        assert_eq!(trail.len(), 12);
+        assert_eq!(apply_subsets(&mut problem, &mut trail), Some(false));
+    }
+
+    #[test]
+    fn test_naked_triple() {
+        // the classic triple where no position holds all three digits
+        let nine: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let problem = first_row(&[&[1, 2], nine, &[2, 3], nine, &[1, 3]]);
+        let house = &problem.layout().houses()[0];
+        assert!(naked_subset(&problem, house, 2).is_none());
+        let step = naked_subset(&problem, house, 3).unwrap();
+        assert_eq!(step.technique, Technique::NakedSubset(3));
+        assert_eq!(step.positions, vec![0, 2, 4]);
+        match step.action {
+            Action::Eliminate(eliminations) => {
+                assert_eq!(eliminations.len(), 6);
+                assert!(eliminations.iter().all(
+                    |elimination| elimination.candidates == [1, 2, 3].iter().copied().collect()
+                ));
+            }
+            action => panic!("unexpected action {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_hidden_pair() {
+        // 1 and 2 only fit into the first two positions of the row
+        let rest: &[u8] = &[3, 4, 5, 6, 7, 8, 9];
+        let problem = first_row(&[
+            &[1, 2, 3, 4],
+            &[1, 2, 5],
+            rest,
+            rest,
+            rest,
+            rest,
+            rest,
+            rest,
+            rest,
+        ]);
+        let house = &problem.layout().houses()[0];
+        let step = hidden_subset(&problem, house, 2).unwrap();
+        assert_eq!(
+            step.describe(problem.layout()),
+            "hidden pair on r1c1 r1c2: r1c1 <> 3,4, r1c2 <> 5"
+        );
+    }
+
+    #[test]
+    fn test_hidden_quad() {
+        let rest: &[u8] = &[5, 6, 7, 8, 9];
+        let many: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let problem = first_row(&[
+            &[1, 2, 5],
+            &[2, 3, 6],
+            &[3, 4, 7],
+            many,
+            rest,
+            rest,
+            rest,
+            rest,
+            rest,
+        ]);
+        let house = &problem.layout().houses()[0];
+        assert!(hidden_subset(&problem, house, 3).is_none());
+        let step = hidden_subset(&problem, house, 4).unwrap();
+        assert_eq!(step.technique, Technique::HiddenSubset(4));
+        assert_eq!(step.positions, vec![0, 1, 2, 3]);
+    }
+}
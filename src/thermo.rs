@@ -0,0 +1,126 @@
+//! Thermometers: the digits along a path of positions strictly increase from the bulb.
+
+use crate::constraint::{Constraint, Elimination};
+use crate::{Candidates, SudokuCandidates};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Thermometer {
+    // starting with the bulb
+    positions: Vec<usize>,
+}
+
+impl Thermometer {
+    pub fn new(positions: Vec<usize>, size: usize) -> Result<Thermometer, String> {
+        if positions.len() < 2 || positions.len() > size {
+            return Err(format!(
+                "Thermometer needs between 2 and {} positions, got {}",
+                size,
+                positions.len()
+            ));
+        }
+        Ok(Thermometer { positions })
+    }
+
+    /// Smallest and largest digit possible at each position of the thermometer,
+    /// given the candidates of all positions.
+    fn bounds(&self, cands: &[Candidates]) -> Vec<(u8, u8)> {
+        let mut bounds = vec![(0, u8::MAX); cands.len()];
+        let mut low: u8 = 0;
+        for (idx, cands) in cands.iter().enumerate() {
+            low = (*cands & Candidates::range(low.saturating_add(1), u8::MAX))
+                .first()
+                .unwrap_or(u8::MAX);
+            bounds[idx].0 = low;
+        }
+        let mut high = u8::MAX;
+        for (idx, cands) in cands.iter().enumerate().rev() {
+            high = (*cands & Candidates::range(1, high.saturating_sub(1)))
+                .last()
+                .unwrap_or(0);
+            bounds[idx].1 = high;
+        }
+        bounds
+    }
+}
+
+impl Constraint for Thermometer {
+    fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    fn has_conflict(&self, problem: &SudokuCandidates) -> bool {
+        // fixed digits need enough room for the positions between them
+        let mut previous: Option<(usize, u8)> = None;
+        for (idx, pos_idx) in self.positions.iter().enumerate() {
+            let cands = problem.get(*pos_idx);
+            if cands.len() != 1 {
+                continue;
+            }
+            let digit = cands.first().unwrap();
+            if let Some((previous_idx, previous_digit)) = previous {
+                if (digit as usize) < previous_digit as usize + idx - previous_idx {
+                    return true;
+                }
+            }
+            previous = Some((idx, digit));
+        }
+        false
+    }
+
+    fn prune(&self, problem: &SudokuCandidates) -> Vec<Elimination> {
+        let cands: Vec<Candidates> = self
+            .positions
+            .iter()
+            .map(|pos_idx| problem.get(*pos_idx))
+            .collect();
+        self.bounds(&cands)
+            .into_iter()
+            .zip(cands)
+            .zip(&self.positions)
+            .filter_map(|(((low, high), cands), pos_idx)| {
+                let outside = cands.without(Candidates::range(low, high));
+                if outside.is_empty() {
+                    None
+                } else {
+                    Some(Elimination {
+                        pos_idx: *pos_idx,
+                        candidates: outside,
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prune_bounds() {
+        let problem = crate::parse_sudoku(&"0".repeat(81)).unwrap();
+        let thermo = Thermometer::new(vec![0, 1, 2, 3], 9).unwrap();
+        let remaining: Vec<Vec<u8>> = thermo
+            .prune(&problem)
+            .iter()
+            .map(|elimination| {
+                problem
+                    .get(elimination.pos_idx)
+                    .without(elimination.candidates)
+                    .iter()
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            remaining,
+            vec![
+                vec![1, 2, 3, 4, 5, 6],
+                vec![2, 3, 4, 5, 6, 7],
+                vec![3, 4, 5, 6, 7, 8],
+                vec![4, 5, 6, 7, 8, 9],
+            ]
+        );
+        assert!(Thermometer::new(vec![0], 9).is_err());
+        assert!(Thermometer::new((0..10).collect(), 9).is_err());
+    }
+}
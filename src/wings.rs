@@ -0,0 +1,235 @@
+//! Wings built from positions with two candidates (bivalue positions).
+//! XY-Wing: a pivot with candidates xy sees two pincers with xz and yz. Whichever digit the
+//! pivot takes, one of the pincers is z, so positions seeing both pincers cannot be z.
+//! XYZ-Wing: like the XY-Wing, but the pivot has the candidates xyz as well, so only positions
+//! seeing the pivot too lose the z.
+//! W-Wing: two positions with the same candidates xy that are connected by a strong link on x,
+//! i.e. a house where x fits into two positions only, one seeing each of the positions.
+//! One of them is y, so positions seeing both cannot be y.
+
+use crate::logic::{eliminations_from, Action, Step, Technique};
+use crate::{Candidates, SudokuCandidates};
+
+fn sees(problem: &SudokuCandidates, pos_idx: usize, other_idx: usize) -> bool {
+    problem.layout().peers(pos_idx).contains(&other_idx)
+}
+
+/// Positions seeing all of the given positions, in ascending order.
+fn common_peers(problem: &SudokuCandidates, positions: &[usize]) -> Vec<usize> {
+    let mut common: Vec<usize> = problem
+        .layout()
+        .peers(positions[0])
+        .iter()
+        .copied()
+        .filter(|pos_idx| {
+            positions[1..]
+                .iter()
+                .all(|other_idx| sees(problem, *pos_idx, *other_idx))
+        })
+        .collect();
+    common.sort_unstable();
+    common
+}
+
+fn wing_step(
+    problem: &SudokuCandidates,
+    technique: Technique,
+    positions: Vec<usize>,
+    houses: Vec<usize>,
+    seen: &[usize],
+    digit: Candidates,
+) -> Option<Step> {
+    let eliminations = eliminations_from(problem, common_peers(problem, seen), digit);
+    if eliminations.is_empty() {
+        None
+    } else {
+        Some(Step {
+            technique,
+            positions,
+            houses,
+            action: Action::Eliminate(eliminations),
+        })
+    }
+}
+
+/// Pairs of bivalue peers of the pivot whose candidates are subsets of `allowed`, with the
+/// digit they have in common.
+fn pincer_pairs(
+    problem: &SudokuCandidates,
+    pivot: usize,
+    allowed: Candidates,
+) -> Vec<(usize, usize, Candidates)> {
+    let pincers: Vec<usize> = problem
+        .layout()
+        .peers(pivot)
+        .iter()
+        .copied()
+        .filter(|pos_idx| {
+            let cands = problem.get(*pos_idx);
+            cands.len() == 2 && cands.without(allowed).is_empty()
+        })
+        .collect();
+    let mut pairs = vec![];
+    for (idx, first) in pincers.iter().enumerate() {
+        for second in &pincers[idx + 1..] {
+            let common = problem.get(*first) & problem.get(*second);
+            if common.len() == 1 {
+                pairs.push((*first, *second, common));
+            }
+        }
+    }
+    pairs
+}
+
+fn find_xy_wing(problem: &SudokuCandidates) -> Option<Step> {
+    for pivot in 0..problem.layout().num_positions() {
+        let pivot_cands = problem.get(pivot);
+        if pivot_cands.len() != 2 {
+            continue;
+        }
+        // the pincers share a different digit of the pivot each and z besides
+        let all = Candidates::all(problem.layout().size());
+        for (first, second, z) in pincer_pairs(problem, pivot, all) {
+            if !(z & pivot_cands).is_empty()
+                || (problem.get(first) | problem.get(second)).without(z) != pivot_cands
+            {
+                continue;
+            }
+            let step = wing_step(
+                problem,
+                Technique::XyWing,
+                vec![pivot, first, second],
+                vec![],
+                &[first, second],
+                z,
+            );
+            if step.is_some() {
+                return step;
+            }
+        }
+    }
+    None
+}
+
+fn find_xyz_wing(problem: &SudokuCandidates) -> Option<Step> {
+    for pivot in 0..problem.layout().num_positions() {
+        let pivot_cands = problem.get(pivot);
+        if pivot_cands.len() != 3 {
+            continue;
+        }
+        for (first, second, z) in pincer_pairs(problem, pivot, pivot_cands) {
+            let step = wing_step(
+                problem,
+                Technique::XyzWing,
+                vec![pivot, first, second],
+                vec![],
+                &[pivot, first, second],
+                z,
+            );
+            if step.is_some() {
+                return step;
+            }
+        }
+    }
+    None
+}
+
+fn find_w_wing(problem: &SudokuCandidates) -> Option<Step> {
+    let layout = problem.layout();
+    let bivalues: Vec<usize> = (0..layout.num_positions())
+        .filter(|pos_idx| problem.get(*pos_idx).len() == 2)
+        .collect();
+    for (idx, first) in bivalues.iter().enumerate() {
+        for second in &bivalues[idx + 1..] {
+            let cands = problem.get(*first);
+            if problem.get(*second) != cands || sees(problem, *first, *second) {
+                continue;
+            }
+            for link_digit in cands.iter() {
+                for (house_idx, house) in layout.houses().iter().enumerate() {
+                    let link: Vec<usize> = house
+                        .positions
+                        .iter()
+                        .copied()
+                        .filter(|pos_idx| problem.get(*pos_idx).contains(link_digit))
+                        .collect();
+                    if link.len() != 2 || link.contains(first) || link.contains(second) {
+                        continue;
+                    }
+                    let connected = (sees(problem, link[0], *first)
+                        && sees(problem, link[1], *second))
+                        || (sees(problem, link[0], *second) && sees(problem, link[1], *first));
+                    if !connected {
+                        continue;
+                    }
+                    let step = wing_step(
+                        problem,
+                        Technique::WWing,
+                        vec![*first, *second, link[0], link[1]],
+                        vec![house_idx],
+                        &[*first, *second],
+                        cands.without(Candidates::single(link_digit)),
+                    );
+                    if step.is_some() {
+                        return step;
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// First XY-Wing, followed by XYZ-Wings and W-Wings if there is none.
+pub(crate) fn find_wing(problem: &SudokuCandidates) -> Option<Step> {
+    find_xy_wing(problem)
+        .or_else(|| find_xyz_wing(problem))
+        .or_else(|| find_w_wing(problem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xy_wing() {
+        // pivot r1c1 with pincers r1c5 and r3c2
+        let problem = SudokuCandidates::pencil_marks(&[(0, &[1, 2]), (4, &[1, 3]), (19, &[2, 3])]);
+        let step = find_wing(&problem).unwrap();
+        assert_eq!(step.technique, Technique::XyWing);
+        assert_eq!(
+            step.describe(problem.layout()),
+            "XY-Wing with pivot r1c1 and pincers r1c5 r3c2: r1c2 <> 3, r1c3 <> 3, r3c4 <> 3, \
+             r3c5 <> 3, r3c6 <> 3"
+        );
+    }
+
+    #[test]
+    fn test_xyz_wing() {
+        let problem =
+            SudokuCandidates::pencil_marks(&[(0, &[1, 2, 3]), (4, &[1, 3]), (10, &[2, 3])]);
+        assert!(find_xy_wing(&problem).is_none());
+        let step = find_wing(&problem).unwrap();
+        assert_eq!(
+            step.describe(problem.layout()),
+            "XYZ-Wing with pivot r1c1 and pincers r1c5 r2c2: r1c2 <> 3, r1c3 <> 3"
+        );
+    }
+
+    #[test]
+    fn test_w_wing() {
+        // r1c1 and r5c9 are connected by the 1s of column 5, which only fit into r1c5 and r5c5
+        let no_one: &[u8] = &[2, 3, 4, 5, 6, 7, 8, 9];
+        let mut marks: Vec<(usize, &[u8])> = vec![(0, &[1, 2]), (44, &[1, 2])];
+        for row_idx in &[1, 2, 3, 5, 6, 7, 8] {
+            marks.push((row_idx * 9 + 4, no_one));
+        }
+        let problem = SudokuCandidates::pencil_marks(&marks);
+        let step = find_wing(&problem).unwrap();
+        assert_eq!(step.technique, Technique::WWing);
+        assert_eq!(
+            step.describe(problem.layout()),
+            "W-Wing on r1c1 r5c9 with strong link in column 5 on r1c5 r5c5: r1c9 <> 2, r5c1 <> 2"
+        );
+    }
+}